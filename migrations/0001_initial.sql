@@ -0,0 +1,54 @@
+-- Plugins that have contributed records; rows are kept when a plugin is removed so data stays attributable
+CREATE TABLE IF NOT EXISTS plugins (
+    name TEXT PRIMARY KEY NOT NULL,
+    first_seen_at INTEGER NOT NULL,
+    last_seen_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS media (
+    plugin TEXT NOT NULL REFERENCES plugins(name) ON DELETE CASCADE,
+    id TEXT NOT NULL,
+    mediatype TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    url TEXT,
+    cover_url TEXT,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, id)
+);
+
+CREATE TABLE IF NOT EXISTS units (
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    number_text TEXT,
+    number REAL,
+    lang TEXT,
+    unit_group TEXT,
+    url TEXT,
+    published_at TEXT,
+    kind TEXT NOT NULL,
+    upload_group TEXT,
+    -- Order in which the plugin listed the unit
+    position INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, id),
+    FOREIGN KEY (plugin, media_id) REFERENCES media(plugin, id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS units_by_media ON units(plugin, media_id);
+
+CREATE TABLE IF NOT EXISTS assets (
+    plugin TEXT NOT NULL,
+    unit_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    mime TEXT,
+    width INTEGER,
+    height INTEGER,
+    kind TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, unit_id, position),
+    FOREIGN KEY (plugin, unit_id) REFERENCES units(plugin, id) ON DELETE CASCADE
+);
@@ -0,0 +1,34 @@
+-- Media the user saved to their personal library
+CREATE TABLE IF NOT EXISTS library_entries (
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    notes TEXT,
+    added_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, media_id),
+    FOREIGN KEY (plugin, media_id) REFERENCES media(plugin, id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS categories (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    position INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS library_categories (
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    PRIMARY KEY (plugin, media_id, category_id),
+    FOREIGN KEY (plugin, media_id) REFERENCES library_entries(plugin, media_id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS library_tags (
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (plugin, media_id, tag),
+    FOREIGN KEY (plugin, media_id) REFERENCES library_entries(plugin, media_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS library_tags_by_tag ON library_tags(tag);
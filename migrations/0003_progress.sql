@@ -0,0 +1,15 @@
+-- Reading/watching progress per unit. Not tied to the units table so progress survives unit list refreshes.
+CREATE TABLE IF NOT EXISTS unit_progress (
+    plugin TEXT NOT NULL,
+    unit_id TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    state TEXT NOT NULL,
+    -- Page index for paged units
+    page INTEGER,
+    -- Playback position for audio/video units
+    position_ms INTEGER,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, unit_id)
+);
+
+CREATE INDEX IF NOT EXISTS unit_progress_by_media ON unit_progress(plugin, media_id);
@@ -0,0 +1,10 @@
+-- Browsing history of viewed media and units
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    plugin TEXT NOT NULL,
+    media_id TEXT,
+    unit_id TEXT,
+    viewed_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS history_by_time ON history(viewed_at);
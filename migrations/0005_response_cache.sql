@@ -0,0 +1,9 @@
+-- Cached plugin responses as JSON, keyed by plugin, operation and operation arguments
+CREATE TABLE IF NOT EXISTS response_cache (
+    plugin TEXT NOT NULL,
+    op TEXT NOT NULL,
+    key TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, op, key)
+);
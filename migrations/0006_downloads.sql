@@ -0,0 +1,32 @@
+-- Offline download jobs, one per unit
+CREATE TABLE IF NOT EXISTS download_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    unit_id TEXT NOT NULL,
+    -- JSON encoded unit as resolved when the job was created
+    unit TEXT NOT NULL,
+    state TEXT NOT NULL,
+    directory TEXT NOT NULL,
+    total_files INTEGER NOT NULL,
+    completed_files INTEGER NOT NULL DEFAULT 0,
+    bytes INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS download_jobs_by_unit ON download_jobs(plugin, unit_id);
+
+CREATE TABLE IF NOT EXISTS download_files (
+    job_id INTEGER NOT NULL REFERENCES download_jobs(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    mime TEXT,
+    kind TEXT NOT NULL,
+    -- File name within the job directory
+    file_name TEXT NOT NULL,
+    done INTEGER NOT NULL DEFAULT 0,
+    bytes INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (job_id, position)
+);
@@ -0,0 +1,18 @@
+-- Extended metadata from `fetchmediadetails`, at most one row per stored media item
+CREATE TABLE IF NOT EXISTS media_details (
+    plugin TEXT NOT NULL,
+    media_id TEXT NOT NULL,
+    status TEXT,
+    content_rating TEXT,
+    rating REAL,
+    year INTEGER,
+    banner_url TEXT,
+    -- JSON encoded lists
+    credits TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    alt_titles TEXT NOT NULL,
+    external_ids TEXT NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (plugin, media_id),
+    FOREIGN KEY (plugin, media_id) REFERENCES media(plugin, id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Request headers and expiry of `library-v2` assets
+ALTER TABLE assets ADD COLUMN headers TEXT NOT NULL DEFAULT '[]';
+ALTER TABLE assets ADD COLUMN expires_at INTEGER;
+ALTER TABLE download_files ADD COLUMN headers TEXT NOT NULL DEFAULT '[]';
@@ -1,11 +1,94 @@
-use anyhow::Result;
-use crate::database::Database;
-use crate::plugins::PluginManager;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use anyhow::{bail, Result};
+use futures::future::join_all;
+use futures::stream::{self, FuturesUnordered, Stream, StreamExt};
+use tracing::{debug, warn};
+use crate::database::{Database, LibraryEntry, LibraryQuery, ResumeInfo};
+use crate::downloads::DownloadManager;
+use crate::proxy::AssetProxy;
+use crate::plugins::{Asset, Media, MediaDetails, MediaType, PluginManager, Unit};
+
+mod cache;
+mod discover;
+mod download;
+mod proxy;
+
+pub use cache::{CacheOp, CachePolicy};
+pub use discover::{DiscoverResults, SourcedFeed};
+
+/// Number of plugins checked at once by `check_updates`
+const UPDATE_CONCURRENCY: usize = 4;
 
 /// Aggregator owns database + plugins and provides higher-level cached & persisted operations.
 pub struct Aggregator {
-    pub db: Database,
+    pub db: Arc<Database>,
     pub pm: PluginManager,
+    /// Set by `enable_downloads`
+    downloads: Option<DownloadManager>,
+    /// Set by `enable_asset_proxy`
+    proxy: Option<AssetProxy>,
+    /// When set, views are not recorded in the history
+    incognito: AtomicBool,
+    cache_policy: RwLock<CachePolicy>,
+    /// Cache entries currently being refreshed in the background
+    revalidating: Arc<Mutex<HashSet<(String, CacheOp, String)>>>,
+}
+
+/// A media item together with the plugin it came from
+#[derive(Debug, Clone)]
+pub struct SourcedMedia {
+    pub plugin: String,
+    pub media: Media,
+}
+
+/// How a single plugin fared during a fan-out operation
+#[derive(Debug)]
+pub struct SourceReport {
+    pub plugin: String,
+    /// Wall time spent waiting on this plugin
+    pub elapsed: Duration,
+    /// Number of items the plugin returned
+    pub count: usize,
+    /// Set when the plugin failed; its items are then missing from the results
+    pub error: Option<anyhow::Error>,
+}
+
+/// Combined results of a search across all plugins
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    pub items: Vec<SourcedMedia>,
+    /// One report per plugin that was queried, in plugin name order
+    pub sources: Vec<SourceReport>,
+}
+
+/// New units discovered for one library entry
+#[derive(Debug, Clone)]
+pub struct EntryUpdate {
+    pub plugin: String,
+    pub media: Media,
+    /// Units that were not stored before, in the order the plugin listed them
+    pub new_units: Vec<Unit>,
+}
+
+/// A library entry whose update check failed
+#[derive(Debug)]
+pub struct UpdateFailure {
+    pub plugin: String,
+    pub media_id: String,
+    pub error: anyhow::Error,
+}
+
+/// Result of `Aggregator::check_updates`
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    /// Entries with at least one new unit
+    pub updated: Vec<EntryUpdate>,
+    pub failed: Vec<UpdateFailure>,
+    /// Number of library entries checked
+    pub checked: usize,
 }
 
 impl Aggregator {
@@ -13,6 +96,198 @@ impl Aggregator {
     pub async fn new() -> Result<Self> {
         let db = Database::new().await?;
         let pm = PluginManager::new().await?;
-        Ok(Self { db, pm })
+        Ok(Self {
+            db: Arc::new(db),
+            pm,
+            downloads: None,
+            proxy: None,
+            incognito: AtomicBool::new(false),
+            cache_policy: RwLock::new(CachePolicy::default()),
+            revalidating: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Enable or disable incognito mode for this session. While enabled, nothing is added to the history.
+    pub fn set_incognito(&self, enabled: bool) {
+        self.incognito.store(enabled, Ordering::Relaxed);
     }
-}
\ No newline at end of file
+
+    pub fn is_incognito(&self) -> bool {
+        self.incognito.load(Ordering::Relaxed)
+    }
+
+    /// List the units of a media item (cached unless refresh is set), recording the view in the history
+    pub async fn fetch_units(&self, plugin: &str, media_id: &str, refresh: bool) -> Result<Vec<Unit>> {
+        let units = self.cached_units(plugin, media_id, refresh).await?;
+        self.record_view(plugin, Some(media_id), None).await;
+        Ok(units)
+    }
+
+    /// List the assets of a unit (cached unless refresh is set), recording the view in the history.
+    /// With the asset proxy enabled, asset URLs point at the proxy.
+    pub async fn fetch_assets(&self, plugin: &str, unit_id: &str, refresh: bool) -> Result<Vec<Asset>> {
+        let mut assets = self.cached_assets(plugin, unit_id, refresh).await?;
+        let media_id = self.db.unit_media_id(plugin, unit_id).await.ok().flatten();
+        self.record_view(plugin, media_id.as_deref(), Some(unit_id)).await;
+        self.proxy_assets(plugin, &mut assets).await?;
+        Ok(assets)
+    }
+
+    /// Extended metadata of a media item. Stored details are returned unless refresh is set or there are none,
+    /// in which case they are fetched from the plugin and stored.
+    pub async fn media_details(&self, plugin: &str, media_id: &str, refresh: bool) -> Result<MediaDetails> {
+        if !refresh {
+            if let Some(details) = self.db.get_media_details(plugin, media_id).await? {
+                return Ok(details);
+            }
+        }
+        let mut details = self.pm.fetch_media_details(plugin, media_id).await?;
+        // Store under the requested id, so later lookups find the row and no other media row is touched
+        if details.media.id != media_id {
+            warn!(plugin, media_id, returned=%details.media.id, "plugin returned details for a different media id");
+            details.media.id = media_id.to_string();
+        }
+        self.db.upsert_media_details(plugin, &details).await?;
+        Ok(details)
+    }
+
+    /// Search every plugin that advertises support for `kind` concurrently.
+    /// Plugins that fail are reported in `sources` rather than failing the whole search.
+    /// Results come from the response cache unless refresh is set.
+    pub async fn search(&self, kind: MediaType, query: &str, refresh: bool) -> SearchResults {
+        let plugins = self.pm.list_plugins();
+        let outcomes = join_all(plugins.iter().map(|plugin| self.search_one(plugin, &kind, query, refresh))).await;
+
+        let mut results = SearchResults::default();
+        for (plugin, outcome) in plugins.into_iter().zip(outcomes) {
+            let Some((elapsed, res)) = outcome else {
+                continue;
+            };
+            match res {
+                Ok(list) => {
+                    results.sources.push(SourceReport { plugin: plugin.clone(), elapsed, count: list.len(), error: None });
+                    results.items.extend(list.into_iter().map(|media| SourcedMedia { plugin: plugin.clone(), media }));
+                }
+                Err(e) => {
+                    warn!(plugin=%plugin, error=%e, "search failed for plugin");
+                    results.sources.push(SourceReport { plugin, elapsed, count: 0, error: Some(e) });
+                }
+            }
+        }
+        debug!(query, count=results.items.len(), sources=results.sources.len(), "search done");
+        results
+    }
+
+    /// Streaming variant of `search`: yields `(plugin, result)` as each supporting plugin replies,
+    /// so one slow source does not hold back the others. Dropping the stream cancels the search.
+    pub fn search_stream<'a>(
+        &'a self,
+        kind: MediaType,
+        query: &'a str,
+        refresh: bool,
+    ) -> impl Stream<Item = (String, Result<Vec<Media>>)> + 'a {
+        self.pm
+            .list_plugins()
+            .into_iter()
+            .map(|plugin| {
+                let kind = kind.clone();
+                async move {
+                    let outcome = self.search_one(&plugin, &kind, query, refresh).await;
+                    (plugin, outcome)
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+            .filter_map(|(plugin, outcome)| async move { outcome.map(|(_, res)| (plugin, res)) })
+    }
+
+    /// Query a single plugin, or None if it does not support the requested media type
+    async fn search_one(&self, plugin: &str, kind: &MediaType, query: &str, refresh: bool) -> Option<(Duration, Result<Vec<Media>>)> {
+        let start = Instant::now();
+        match self.capabilities(plugin, false).await {
+            Ok(caps) if !caps.media_types.contains(kind) => return None,
+            Ok(_) => {}
+            Err(e) => return Some((start.elapsed(), Err(e))),
+        }
+        let start = Instant::now();
+        let res = self.fetch_media_list(plugin, kind.clone(), query, refresh).await;
+        Some((start.elapsed(), res))
+    }
+
+    /// Resume information for a stored media item. If no units are stored yet they are
+    /// fetched from the owning plugin first, so "next unit" works for freshly saved entries.
+    pub async fn resume(&self, plugin: &str, media_id: &str) -> Result<ResumeInfo> {
+        if self.db.get_units(plugin, media_id).await?.is_empty() && self.db.get_media(plugin, media_id).await?.is_some() {
+            let units = self.cached_units(plugin, media_id, false).await?;
+            self.db.replace_units(plugin, media_id, &units).await?;
+        }
+        self.db.resume_info(plugin, media_id).await
+    }
+
+    /// Append a history entry unless in incognito mode. History is best-effort and never fails the caller.
+    async fn record_view(&self, plugin: &str, media_id: Option<&str>, unit_id: Option<&str>) {
+        if self.is_incognito() {
+            return;
+        }
+        if let Err(e) = self.db.record_history(plugin, media_id, unit_id).await {
+            debug!(plugin, error=%e, "history not recorded");
+        }
+    }
+
+    /// Check every library entry for new units and store them.
+    /// Entries of one plugin are checked one after another so the plugin's `rate_limit_ms` is honoured
+    /// without calls queueing past their timeout; up to `UPDATE_CONCURRENCY` plugins are checked at once.
+    /// An entry with no stored units is only synced, since every unit would otherwise count as new.
+    pub async fn check_updates(&self) -> Result<UpdateReport> {
+        let entries = self.db.list_library(&LibraryQuery::default()).await?;
+        let mut by_plugin: BTreeMap<String, Vec<LibraryEntry>> = BTreeMap::new();
+        for entry in entries {
+            by_plugin.entry(entry.plugin.clone()).or_default().push(entry);
+        }
+
+        let outcomes: Vec<Vec<(LibraryEntry, Result<Vec<Unit>>)>> = stream::iter(by_plugin)
+            .map(|(plugin, entries)| async move {
+                let mut out = Vec::with_capacity(entries.len());
+                for entry in entries {
+                    let res = self.check_entry(&plugin, &entry.media.id).await;
+                    out.push((entry, res));
+                }
+                out
+            })
+            .buffer_unordered(UPDATE_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut report = UpdateReport::default();
+        for (entry, res) in outcomes.into_iter().flatten() {
+            report.checked += 1;
+            match res {
+                Ok(new_units) if new_units.is_empty() => {}
+                Ok(new_units) => report.updated.push(EntryUpdate { plugin: entry.plugin, media: entry.media, new_units }),
+                Err(error) => {
+                    warn!(plugin=%entry.plugin, media_id=%entry.media.id, error=%error, "update check failed");
+                    report.failed.push(UpdateFailure { plugin: entry.plugin, media_id: entry.media.id, error });
+                }
+            }
+        }
+        debug!(checked=report.checked, updated=report.updated.len(), failed=report.failed.len(), "update check done");
+        Ok(report)
+    }
+
+    /// Fetch and store the units of one media item, returning those not stored before.
+    /// An empty listing for an entry with stored units is treated as a failure and changes nothing,
+    /// since `library` world plugins report errors as empty lists.
+    async fn check_entry(&self, plugin: &str, media_id: &str) -> Result<Vec<Unit>> {
+        let stored = self.db.get_units(plugin, media_id).await?;
+        let units = self.pm.fetch_units(plugin, media_id).await?;
+        if units.is_empty() && !stored.is_empty() {
+            bail!("plugin returned no units for an entry with {} stored units", stored.len());
+        }
+        self.db.replace_units(plugin, media_id, &units).await?;
+        self.store_cached(plugin, CacheOp::Units, media_id, &units).await;
+        if stored.is_empty() {
+            return Ok(Vec::new());
+        }
+        let known: HashSet<&str> = stored.iter().map(|u| u.id.as_str()).collect();
+        Ok(units.into_iter().filter(|u| !known.contains(u.id.as_str())).collect())
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::debug;
+
+use crate::aggregator::Aggregator;
+use crate::database::{cache_put, media_type_to_str, now_secs};
+use crate::plugins::{Asset, Media, MediaType, ProviderCapabilities, Unit};
+
+/// Plugin operations whose responses are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheOp {
+    MediaList,
+    Units,
+    Assets,
+    Capabilities,
+}
+
+impl CacheOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheOp::MediaList => "fetchmedialist",
+            CacheOp::Units => "fetchunits",
+            CacheOp::Assets => "fetchassets",
+            CacheOp::Capabilities => "getcapabilities",
+        }
+    }
+}
+
+/// A response that may be stored in the cache
+pub(crate) trait CacheValue: Serialize + DeserializeOwned + Send + 'static {
+    /// Whether the response is worth storing. `library` world plugins report failures as empty lists,
+    /// so empty lists are never cached rather than served as results for a whole TTL.
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + 'static> CacheValue for Vec<T> {
+    fn cacheable(&self) -> bool {
+        !self.is_empty()
+    }
+}
+
+impl CacheValue for ProviderCapabilities {}
+
+/// Time-to-live settings for the response cache. A zero TTL disables caching for that operation.
+#[derive(Debug, Clone)]
+pub struct CachePolicy {
+    ttls: HashMap<CacheOp, Duration>,
+    plugin_ttls: HashMap<(String, CacheOp), Duration>,
+    /// How long past its TTL an entry may still be served while a refresh runs in the background
+    pub stale_while_revalidate: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        let ttls = HashMap::from([
+            (CacheOp::MediaList, Duration::from_secs(30 * 60)),
+            (CacheOp::Units, Duration::from_secs(6 * 60 * 60)),
+            (CacheOp::Assets, Duration::from_secs(10 * 60)),
+            (CacheOp::Capabilities, Duration::from_secs(24 * 60 * 60)),
+        ]);
+        Self { ttls, plugin_ttls: HashMap::new(), stale_while_revalidate: Duration::from_secs(10 * 60) }
+    }
+}
+
+impl CachePolicy {
+    /// Set the default TTL of an operation
+    pub fn set_ttl(&mut self, op: CacheOp, ttl: Duration) {
+        self.ttls.insert(op, ttl);
+    }
+
+    /// Override the TTL of an operation for one plugin
+    pub fn set_plugin_ttl(&mut self, plugin: &str, op: CacheOp, ttl: Duration) {
+        self.plugin_ttls.insert((plugin.to_string(), op), ttl);
+    }
+
+    /// Effective TTL for a plugin's operation
+    pub fn ttl(&self, plugin: &str, op: CacheOp) -> Duration {
+        self.plugin_ttls
+            .get(&(plugin.to_string(), op))
+            .or_else(|| self.ttls.get(&op))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Cached plugin calls. With `refresh` set the plugin is always called and the cache updated.
+impl Aggregator {
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.cache_policy.read().unwrap().clone()
+    }
+
+    pub fn set_cache_policy(&self, policy: CachePolicy) {
+        *self.cache_policy.write().unwrap() = policy;
+    }
+
+    /// Search one plugin for media, served from the cache when fresh
+    pub async fn fetch_media_list(&self, plugin: &str, kind: MediaType, query: &str, refresh: bool) -> Result<Vec<Media>> {
+        let key = media_list_key(&kind, query);
+        let fetch = self.pm.fetch_media_list_task(plugin, kind, query);
+        self.cached(plugin, CacheOp::MediaList, &key, refresh, fetch).await
+    }
+
+    /// Capabilities of one plugin, served from the cache when fresh so the plugin need not be loaded
+    pub async fn capabilities(&self, plugin: &str, refresh: bool) -> Result<ProviderCapabilities> {
+        let fetch = self.pm.get_capabilities_task(plugin, refresh);
+        self.cached(plugin, CacheOp::Capabilities, "", refresh, fetch).await
+    }
+
+    /// Drop cached responses of a plugin, optionally only for one operation
+    pub async fn invalidate_cache(&self, plugin: Option<&str>, op: Option<CacheOp>) -> Result<u64> {
+        self.db.cache_invalidate(plugin, op.map(|o| o.as_str()), None).await
+    }
+
+    /// Drop the cached unit list of a media item
+    pub async fn invalidate_units(&self, plugin: &str, media_id: &str) -> Result<()> {
+        self.db.cache_invalidate(Some(plugin), Some(CacheOp::Units.as_str()), Some(media_id)).await?;
+        Ok(())
+    }
+
+    /// Drop the cached assets of a unit
+    pub async fn invalidate_assets(&self, plugin: &str, unit_id: &str) -> Result<()> {
+        self.db.cache_invalidate(Some(plugin), Some(CacheOp::Assets.as_str()), Some(unit_id)).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn cached_units(&self, plugin: &str, media_id: &str, refresh: bool) -> Result<Vec<Unit>> {
+        let fetch = self.pm.fetch_units_task(plugin, media_id);
+        self.cached(plugin, CacheOp::Units, media_id, refresh, fetch).await
+    }
+
+    /// Assets of a unit, refetched when any cached asset URL has expired
+    pub(crate) async fn cached_assets(&self, plugin: &str, unit_id: &str, refresh: bool) -> Result<Vec<Asset>> {
+        let fetch = self.pm.fetch_assets_task(plugin, unit_id);
+        let assets = self.cached(plugin, CacheOp::Assets, unit_id, refresh, fetch).await?;
+        if refresh || !assets.iter().any(Asset::is_expired) {
+            return Ok(assets);
+        }
+        debug!(plugin, unit_id, "cached asset urls expired");
+        let fetch = self.pm.fetch_assets_task(plugin, unit_id);
+        self.cached(plugin, CacheOp::Assets, unit_id, true, fetch).await
+    }
+
+    /// Store a response obtained outside the cache (e.g. by the update checker). Best-effort.
+    pub(crate) async fn store_cached<T: CacheValue>(&self, plugin: &str, op: CacheOp, key: &str, value: &T) {
+        if !value.cacheable() || self.cache_policy().ttl(plugin, op).is_zero() {
+            return;
+        }
+        let res = match serde_json::to_string(value) {
+            Ok(json) => self.db.cache_put(plugin, op.as_str(), key, &json).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = res {
+            debug!(plugin, op=op.as_str(), error=%e, "response not cached");
+        }
+    }
+
+    /// Serve from the cache when fresh; when stale but within the revalidation window, serve the stale
+    /// value and refresh in the background; otherwise await `fetch` and store its result.
+    /// `fetch` is only polled if the plugin actually needs to be called.
+    async fn cached<T, F>(&self, plugin: &str, op: CacheOp, key: &str, refresh: bool, fetch: F) -> Result<T>
+    where
+        T: CacheValue,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let policy = self.cache_policy();
+        let ttl = policy.ttl(plugin, op);
+        if !refresh && !ttl.is_zero() {
+            if let Ok(Some(hit)) = self.db.cache_get(plugin, op.as_str(), key).await {
+                let age = Duration::from_secs(now_secs().saturating_sub(hit.fetched_at).max(0) as u64);
+                match serde_json::from_str::<T>(&hit.payload) {
+                    Ok(value) if age <= ttl => return Ok(value),
+                    Ok(value) if age <= ttl + policy.stale_while_revalidate => {
+                        self.revalidate(plugin, op, key, fetch);
+                        return Ok(value);
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!(plugin, op=op.as_str(), error=%e, "discarding undecodable cache entry"),
+                }
+            }
+        }
+        let value = fetch.await?;
+        self.store_cached(plugin, op, key, &value).await;
+        Ok(value)
+    }
+
+    /// Refresh a stale entry in the background, at most once at a time per entry
+    fn revalidate<T, F>(&self, plugin: &str, op: CacheOp, key: &str, fetch: F)
+    where
+        T: CacheValue,
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let Ok(pool) = self.db.pool().cloned() else {
+            return;
+        };
+        let flight = (plugin.to_string(), op, key.to_string());
+        if !self.revalidating.lock().unwrap().insert(flight.clone()) {
+            return;
+        }
+        let revalidating = self.revalidating.clone();
+        tokio::spawn(async move {
+            let (plugin, op, key) = &flight;
+            let res = match fetch.await {
+                // Keep serving the stale entry rather than replacing it with a likely failure
+                Ok(value) if !value.cacheable() => Ok(()),
+                Ok(value) => match serde_json::to_string(&value) {
+                    Ok(json) => cache_put(&pool, plugin, op.as_str(), key, &json).await,
+                    Err(e) => Err(e.into()),
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                debug!(plugin=%plugin, op=op.as_str(), error=%e, "background revalidation failed");
+            }
+            revalidating.lock().unwrap().remove(&flight);
+        });
+    }
+}
+
+fn media_list_key(kind: &MediaType, query: &str) -> String {
+    format!("{}\u{1f}{}", media_type_to_str(kind), query)
+}
@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use futures::future::join_all;
+use tracing::{debug, warn};
+
+use crate::aggregator::{Aggregator, SourceReport};
+use crate::plugins::{Feed, FeedKind, Media, Page};
+
+/// First page of one plugin's feed
+#[derive(Debug, Clone)]
+pub struct SourcedFeed {
+    pub plugin: String,
+    pub feed: Feed,
+    pub page: Page<Media>,
+}
+
+/// Combined feeds of all plugins, e.g. for a home screen
+#[derive(Debug, Default)]
+pub struct DiscoverResults {
+    /// Feeds in plugin name order, each plugin's feeds in the order it listed them
+    pub feeds: Vec<SourcedFeed>,
+    /// One report per plugin with matching feeds, in plugin name order; `count` is the number of items fetched
+    pub sources: Vec<SourceReport>,
+}
+
+impl Aggregator {
+    /// Fetch the first page of every feed of `kind` (all feeds when None) from every plugin concurrently.
+    /// Plugins without matching feeds are left out; plugins that fail are reported in `sources`.
+    pub async fn discover(&self, kind: Option<&FeedKind>) -> DiscoverResults {
+        let plugins = self.pm.list_plugins();
+        let outcomes = join_all(plugins.iter().map(|plugin| self.discover_one(plugin, kind))).await;
+
+        let mut results = DiscoverResults::default();
+        for (plugin, (elapsed, res)) in plugins.into_iter().zip(outcomes) {
+            match res {
+                Ok(feeds) if feeds.is_empty() => {}
+                Ok(feeds) => {
+                    let count = feeds.iter().map(|(_, page)| page.items.len()).sum();
+                    results.sources.push(SourceReport { plugin: plugin.clone(), elapsed, count, error: None });
+                    results.feeds.extend(feeds.into_iter().map(|(feed, page)| SourcedFeed { plugin: plugin.clone(), feed, page }));
+                }
+                Err(e) => {
+                    warn!(plugin=%plugin, error=%e, "discover failed for plugin");
+                    results.sources.push(SourceReport { plugin, elapsed, count: 0, error: Some(e) });
+                }
+            }
+        }
+        debug!(feeds=results.feeds.len(), sources=results.sources.len(), "discover done");
+        results
+    }
+
+    /// First pages of one plugin's matching feeds, fetched one after another to respect its rate limit
+    async fn discover_one(&self, plugin: &str, kind: Option<&FeedKind>) -> (Duration, Result<Vec<(Feed, Page<Media>)>>) {
+        let start = Instant::now();
+        let res = async {
+            let mut out = Vec::new();
+            for feed in self.pm.list_feeds(plugin).await? {
+                if kind.is_some_and(|k| *k != feed.kind) {
+                    continue;
+                }
+                let page = self.pm.fetch_feed(plugin, &feed.id, None).await?;
+                out.push((feed, page));
+            }
+            Ok(out)
+        }
+        .await;
+        (start.elapsed(), res)
+    }
+}
@@ -0,0 +1,126 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use anyhow::{anyhow, bail, Result};
+
+use crate::aggregator::Aggregator;
+use crate::database::{DownloadJob, DownloadState};
+use crate::downloads::{DownloadManager, DownloadOptions};
+use crate::export::{export_book, ExportBook, ExportChapter, ExportFormat};
+use crate::plugins::{AssetKind, Unit};
+
+impl Aggregator {
+    /// Store offline downloads under `dir`
+    pub fn enable_downloads(&mut self, dir: &Path, options: DownloadOptions) -> Result<()> {
+        self.downloads = Some(DownloadManager::new(self.db.clone(), dir, options)?);
+        Ok(())
+    }
+
+    pub fn downloads(&self) -> Result<&DownloadManager> {
+        self.downloads.as_ref().ok_or_else(|| anyhow!("downloads are not enabled"))
+    }
+
+    /// Download every asset of a unit for offline use. Assets are resolved through the owning plugin
+    /// and fetched only from its allowed hosts. Returns the job id; progress is reported via `downloads().subscribe()`.
+    pub async fn download_unit(&self, plugin: &str, media_id: &str, unit: &Unit) -> Result<i64> {
+        let downloads = self.downloads()?;
+        // Asset links are often short-lived, so always ask the plugin for fresh ones
+        let assets = self.cached_assets(plugin, &unit.id, true).await?;
+        // Built-in providers hand out file URLs that are read with `PluginManager::read_asset`, not fetched
+        if assets.iter().any(|a| a.url.starts_with("file:")) {
+            bail!("unit {} of plugin {} is stored locally and cannot be downloaded", unit.id, plugin);
+        }
+        let policy = self.pm.host_policy_task(plugin).await?;
+        let transform = self.pm.asset_transform(plugin).await?;
+        downloads.enqueue(plugin, media_id, unit, &assets, policy, transform).await
+    }
+
+    /// Continue a paused or failed download with freshly resolved asset links
+    pub async fn resume_download(&self, job_id: i64) -> Result<()> {
+        let downloads = self.downloads()?;
+        let job = self.db.get_download_job(job_id).await?
+            .ok_or_else(|| anyhow!("download job not found: {}", job_id))?;
+        if job.state == DownloadState::Completed || downloads.is_active(job_id) {
+            return Ok(());
+        }
+        if job.state == DownloadState::Cancelled {
+            bail!("download job {} was cancelled; download the unit again", job_id);
+        }
+        let assets = self.cached_assets(&job.plugin, &job.unit.id, true).await?;
+        if assets.len() != job.total_files as usize {
+            bail!(
+                "assets of unit {} changed since the download started ({} now, {} before); download it again",
+                job.unit.id, assets.len(), job.total_files
+            );
+        }
+        self.db.refresh_download_urls(job_id, &assets).await?;
+        let policy = self.pm.host_policy_task(&job.plugin).await?;
+        let transform = self.pm.asset_transform(&job.plugin).await?;
+        downloads.start(job_id, policy, transform)
+    }
+}
+
+impl Aggregator {
+    /// Export a downloaded unit to `dest` as a CBZ or fixed-layout EPUB
+    pub async fn export_unit(&self, plugin: &str, unit_id: &str, format: ExportFormat, dest: &Path) -> Result<()> {
+        let job = self.db.find_download_job(plugin, unit_id).await?
+            .filter(|j| j.state == DownloadState::Completed)
+            .ok_or_else(|| anyhow!("unit {} of plugin {} has not been downloaded", unit_id, plugin))?;
+        let media = self.db.get_media(plugin, &job.media_id).await?;
+        let title = job.unit.title.clone();
+        let chapter = self.export_chapter(job).await?;
+        write_export(ExportBook { title, media, chapters: vec![chapter] }, format, dest).await
+    }
+
+    /// Export all downloaded units of a media item as one book, ordered by unit number.
+    /// With `group` set, only units of that group (volume/season/part) are included.
+    pub async fn export_group(
+        &self,
+        plugin: &str,
+        media_id: &str,
+        group: Option<&str>,
+        format: ExportFormat,
+        dest: &Path,
+    ) -> Result<()> {
+        let mut jobs: Vec<DownloadJob> = self.db.media_download_jobs(plugin, media_id).await?
+            .into_iter()
+            .filter(|j| j.state == DownloadState::Completed)
+            .filter(|j| group.is_none() || j.unit.group.as_deref() == group)
+            .collect();
+        if jobs.is_empty() {
+            bail!("no downloaded units to export for {}/{}", plugin, media_id);
+        }
+        // Units without a number keep their download order after the numbered ones
+        jobs.sort_by(|a, b| match (a.unit.number, b.unit.number) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        let media = self.db.get_media(plugin, media_id).await?;
+        let base = media.as_ref().map(|m| m.title.clone()).unwrap_or_else(|| media_id.to_string());
+        let title = match group {
+            Some(group) => format!("{} - {}", base, group),
+            None => base,
+        };
+        let mut chapters = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            chapters.push(self.export_chapter(job).await?);
+        }
+        write_export(ExportBook { title, media, chapters }, format, dest).await
+    }
+
+    /// Page images of a completed download job
+    async fn export_chapter(&self, job: DownloadJob) -> Result<ExportChapter> {
+        let pages = self.db.download_files(job.id).await?
+            .iter()
+            .filter(|f| matches!(f.kind, AssetKind::Page | AssetKind::Image))
+            .map(|f| f.path(&job))
+            .collect();
+        Ok(ExportChapter { unit: job.unit, pages })
+    }
+}
+
+async fn write_export(book: ExportBook, format: ExportFormat, dest: &Path) -> Result<()> {
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || export_book(&book, format, &dest)).await?
+}
@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use crate::aggregator::Aggregator;
+use crate::plugins::Asset;
+use crate::proxy::{AssetProxy, ProxyOptions};
+
+impl Aggregator {
+    /// Start the loopback asset proxy. From then on `fetch_assets` hands out proxy URLs.
+    /// Replaces a running proxy, invalidating the URLs it handed out.
+    pub async fn enable_asset_proxy(&mut self, options: ProxyOptions) -> Result<()> {
+        self.proxy = Some(AssetProxy::start(options).await?);
+        Ok(())
+    }
+
+    pub fn asset_proxy(&self) -> Result<&AssetProxy> {
+        self.proxy.as_ref().ok_or_else(|| anyhow!("asset proxy is not enabled"))
+    }
+
+    /// Rewrite asset URLs to go through the proxy, if it is enabled
+    pub(crate) async fn proxy_assets(&self, plugin: &str, assets: &mut [Asset]) -> Result<()> {
+        if let Some(proxy) = &self.proxy {
+            let policy = self.pm.host_policy_task(plugin).await?;
+            let reader = self.pm.asset_reader(plugin)?;
+            let transform = self.pm.asset_transform(plugin).await;
+            if let Err(e) = &transform {
+                warn!(plugin=%plugin, error=%e, "could not determine asset transform; its images will not be served");
+            }
+            proxy.rewrite(plugin, assets, &policy, reader, &transform);
+        }
+        Ok(())
+    }
+}
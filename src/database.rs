@@ -1,18 +1,169 @@
-use std::path::PathBuf;
-use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use tokio::sync::{watch, OnceCell};
+use tracing::info;
+
+mod convert;
+mod downloads;
+mod history;
+mod library;
+mod media;
+mod progress;
+mod response_cache;
+
+pub use downloads::{DownloadFile, DownloadJob, DownloadState};
+pub use history::HistoryEntry;
+pub use library::{LibraryEntry, LibraryQuery, LibrarySort};
+pub use progress::{ResumeInfo, UnitProgress, UnitState};
+pub use response_cache::CachedResponse;
+
+pub(crate) use convert::{media_type_to_str, now_secs};
+pub(crate) use response_cache::cache_put;
+
+/// Migrations embedded from `migrations/` at build time
+static MIGRATOR: Migrator = sqlx::migrate!();
 
 /// Manages persistent storage of aggregated data
 pub struct Database {
+    pool: OnceCell<SqlitePool>,
+    progress: watch::Sender<MigrationProgress>,
+}
 
+/// A single embedded schema migration
+#[derive(Debug, Clone)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+}
+
+/// Which embedded migrations have been applied to the connected database
+#[derive(Debug, Clone)]
+pub struct MigrationStatus {
+    /// Highest applied version, None for a fresh database
+    pub current_version: Option<i64>,
+    /// Highest version embedded in this build
+    pub latest_version: Option<i64>,
+    pub applied: Vec<MigrationInfo>,
+    pub pending: Vec<MigrationInfo>,
+}
+
+/// Live progress of a migration run, published while `connect` upgrades the schema
+#[derive(Debug, Clone, Default)]
+pub struct MigrationProgress {
+    /// Number of migrations this run has to apply
+    pub total: usize,
+    /// Number applied so far
+    pub completed: usize,
+    /// Migration currently being applied
+    pub current: Option<MigrationInfo>,
+    pub finished: bool,
 }
 
 impl Database {
     pub async fn new() -> Result<Self> {
-        Ok(Database {})
+        let (progress, _) = watch::channel(MigrationProgress::default());
+        Ok(Database { pool: OnceCell::new(), progress })
     }
 
-    pub async fn connect(&self, database_url: &PathBuf) -> Result<()> {
-        // TODO Implement connection logic here
+    /// Open (creating if needed) the SQLite database at the given path.
+    /// If run_migrations is true, pending embedded migrations are applied before returning.
+    pub async fn connect(&self, database_path: &Path, run_migrations: bool) -> Result<()> {
+        let options = SqliteConnectOptions::new()
+            .filename(database_path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        let pool = self.pool.get_or_try_init(|| async {
+            SqlitePoolOptions::new()
+                .max_connections(4)
+                .connect_with(options)
+                .await
+                .map_err(|e| anyhow!("failed to open database {}: {}", database_path.display(), e))
+        }).await?;
+        info!(path=%database_path.display(), "connected to database");
+        if run_migrations {
+            self.migrate(pool).await?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Subscribe to migration progress, e.g. to drive an upgrade screen while `connect` runs
+    pub fn subscribe_migrations(&self) -> watch::Receiver<MigrationProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Report applied and pending migrations for the connected database
+    pub async fn migration_status(&self) -> Result<MigrationStatus> {
+        let mut conn = self.pool()?.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        let applied_versions: HashSet<i64> = conn
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|m| m.version)
+            .collect();
+        let mut status = MigrationStatus {
+            current_version: None,
+            latest_version: None,
+            applied: Vec::new(),
+            pending: Vec::new(),
+        };
+        for migration in MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+            let info = MigrationInfo { version: migration.version, description: migration.description.to_string() };
+            status.latest_version = status.latest_version.max(Some(migration.version));
+            if applied_versions.contains(&migration.version) {
+                status.current_version = status.current_version.max(Some(migration.version));
+                status.applied.push(info);
+            } else {
+                status.pending.push(info);
+            }
+        }
+        Ok(status)
+    }
+
+    /// The connection pool, or an error if `connect` has not succeeded yet
+    pub(crate) fn pool(&self) -> Result<&SqlitePool> {
+        self.pool.get().ok_or_else(|| anyhow!("database not connected"))
+    }
+
+    /// Apply pending migrations one at a time, publishing progress after each
+    async fn migrate(&self, pool: &SqlitePool) -> Result<()> {
+        let mut conn = pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        if let Some(version) = conn.dirty_version().await? {
+            return Err(anyhow!("database migration {} previously failed; manual repair required", version));
+        }
+        let applied: HashMap<i64, Vec<u8>> = conn
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|m| (m.version, m.checksum.into_owned()))
+            .collect();
+
+        let mut pending = Vec::new();
+        for migration in MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+            match applied.get(&migration.version) {
+                Some(checksum) if *checksum != *migration.checksum => {
+                    return Err(anyhow!("applied migration {} does not match the embedded version", migration.version));
+                }
+                Some(_) => {}
+                None => pending.push(migration),
+            }
+        }
+
+        let total = pending.len();
+        self.progress.send_replace(MigrationProgress { total, ..Default::default() });
+        for (completed, migration) in pending.into_iter().enumerate() {
+            let info = MigrationInfo { version: migration.version, description: migration.description.to_string() };
+            self.progress.send_replace(MigrationProgress { total, completed, current: Some(info), finished: false });
+            let elapsed = conn.apply(migration).await
+                .map_err(|e| anyhow!("migration {} failed: {}", migration.version, e))?;
+            info!(version = migration.version, description = %migration.description, ?elapsed, "applied database migration");
+        }
+        self.progress.send_replace(MigrationProgress { total, completed: total, current: None, finished: true });
+        Ok(())
+    }
+}
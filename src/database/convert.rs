@@ -0,0 +1,110 @@
+//! Text encodings for WIT variants stored in SQLite columns.
+//! Known cases use their WIT names; `other(x)` is stored as `other:x`.
+
+use crate::plugins::{AssetKind, ContentRating, MediaType, PublicationStatus, UnitKind};
+
+pub(crate) fn media_type_to_str(kind: &MediaType) -> String {
+    match kind {
+        MediaType::Paged => "paged".to_string(),
+        MediaType::Audio => "audio".to_string(),
+        MediaType::Video => "video".to_string(),
+        MediaType::Other(s) => format!("other:{}", s),
+    }
+}
+
+pub(crate) fn media_type_from_str(s: &str) -> MediaType {
+    match s {
+        "paged" => MediaType::Paged,
+        "audio" => MediaType::Audio,
+        "video" => MediaType::Video,
+        other => MediaType::Other(other.strip_prefix("other:").unwrap_or(other).to_string()),
+    }
+}
+
+pub(crate) fn unit_kind_to_str(kind: &UnitKind) -> String {
+    match kind {
+        UnitKind::Chapter => "chapter".to_string(),
+        UnitKind::Episode => "episode".to_string(),
+        UnitKind::Section => "section".to_string(),
+        UnitKind::Other(s) => format!("other:{}", s),
+    }
+}
+
+pub(crate) fn unit_kind_from_str(s: &str) -> UnitKind {
+    match s {
+        "chapter" => UnitKind::Chapter,
+        "episode" => UnitKind::Episode,
+        "section" => UnitKind::Section,
+        other => UnitKind::Other(other.strip_prefix("other:").unwrap_or(other).to_string()),
+    }
+}
+
+pub(crate) fn asset_kind_to_str(kind: &AssetKind) -> String {
+    match kind {
+        AssetKind::Page => "page".to_string(),
+        AssetKind::Image => "image".to_string(),
+        AssetKind::Audio => "audio".to_string(),
+        AssetKind::Video => "video".to_string(),
+        AssetKind::Subtitle => "subtitle".to_string(),
+        AssetKind::File => "file".to_string(),
+        AssetKind::Other(s) => format!("other:{}", s),
+    }
+}
+
+pub(crate) fn asset_kind_from_str(s: &str) -> AssetKind {
+    match s {
+        "page" => AssetKind::Page,
+        "image" => AssetKind::Image,
+        "audio" => AssetKind::Audio,
+        "video" => AssetKind::Video,
+        "subtitle" => AssetKind::Subtitle,
+        "file" => AssetKind::File,
+        other => AssetKind::Other(other.strip_prefix("other:").unwrap_or(other).to_string()),
+    }
+}
+
+pub(crate) fn publication_status_to_str(status: PublicationStatus) -> &'static str {
+    match status {
+        PublicationStatus::Ongoing => "ongoing",
+        PublicationStatus::Completed => "completed",
+        PublicationStatus::Hiatus => "hiatus",
+        PublicationStatus::Cancelled => "cancelled",
+    }
+}
+
+pub(crate) fn publication_status_from_str(s: &str) -> Option<PublicationStatus> {
+    match s {
+        "ongoing" => Some(PublicationStatus::Ongoing),
+        "completed" => Some(PublicationStatus::Completed),
+        "hiatus" => Some(PublicationStatus::Hiatus),
+        "cancelled" => Some(PublicationStatus::Cancelled),
+        _ => None,
+    }
+}
+
+pub(crate) fn content_rating_to_str(rating: ContentRating) -> &'static str {
+    match rating {
+        ContentRating::Safe => "safe",
+        ContentRating::Suggestive => "suggestive",
+        ContentRating::Mature => "mature",
+        ContentRating::Explicit => "explicit",
+    }
+}
+
+pub(crate) fn content_rating_from_str(s: &str) -> Option<ContentRating> {
+    match s {
+        "safe" => Some(ContentRating::Safe),
+        "suggestive" => Some(ContentRating::Suggestive),
+        "mature" => Some(ContentRating::Mature),
+        "explicit" => Some(ContentRating::Explicit),
+        _ => None,
+    }
+}
+
+/// Current time as unix seconds, the timestamp format used throughout the schema
+pub(crate) fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
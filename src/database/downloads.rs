@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+
+use crate::database::convert::*;
+use crate::database::Database;
+use crate::plugins::{Asset, AssetHeader, AssetKind, Unit};
+
+/// Lifecycle of a download job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl DownloadState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DownloadState::Queued => "queued",
+            DownloadState::Running => "running",
+            DownloadState::Paused => "paused",
+            DownloadState::Completed => "completed",
+            DownloadState::Failed => "failed",
+            DownloadState::Cancelled => "cancelled",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "running" => DownloadState::Running,
+            "paused" => DownloadState::Paused,
+            "completed" => DownloadState::Completed,
+            "failed" => DownloadState::Failed,
+            "cancelled" => DownloadState::Cancelled,
+            _ => DownloadState::Queued,
+        }
+    }
+}
+
+/// A stored download job
+#[derive(Debug, Clone)]
+pub struct DownloadJob {
+    pub id: i64,
+    pub plugin: String,
+    pub media_id: String,
+    pub unit: Unit,
+    pub state: DownloadState,
+    /// Directory holding the downloaded files
+    pub directory: PathBuf,
+    pub total_files: u32,
+    pub completed_files: u32,
+    pub bytes: u64,
+    pub error: Option<String>,
+    /// Unix seconds
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A single file of a download job
+#[derive(Debug, Clone)]
+pub struct DownloadFile {
+    pub job_id: i64,
+    pub position: u32,
+    pub url: String,
+    pub mime: Option<String>,
+    pub kind: AssetKind,
+    /// Headers the provider requires on requests for the file
+    pub headers: Vec<AssetHeader>,
+    pub file_name: String,
+    pub done: bool,
+    pub bytes: u64,
+}
+
+impl DownloadFile {
+    pub fn path(&self, job: &DownloadJob) -> PathBuf {
+        job.directory.join(&self.file_name)
+    }
+}
+
+/// Download job bookkeeping
+impl Database {
+    /// Create a download job for a unit with one file per asset. `file_names` must match `assets`.
+    pub async fn create_download_job(
+        &self,
+        plugin: &str,
+        media_id: &str,
+        unit: &Unit,
+        directory: &std::path::Path,
+        assets: &[Asset],
+        file_names: &[String],
+    ) -> Result<i64> {
+        let now = now_secs();
+        let mut tx = self.pool()?.begin().await?;
+        let res = sqlx::query(
+            "INSERT INTO download_jobs (plugin, media_id, unit_id, unit, state, directory, total_files, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(plugin)
+        .bind(media_id)
+        .bind(&unit.id)
+        .bind(serde_json::to_string(unit)?)
+        .bind(DownloadState::Queued.as_str())
+        .bind(directory.to_string_lossy().to_string())
+        .bind(assets.len() as i64)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| anyhow!("failed to create download job for {}/{}: {}", plugin, unit.id, e))?;
+        let job_id = res.last_insert_rowid();
+        for (position, (asset, file_name)) in assets.iter().zip(file_names).enumerate() {
+            sqlx::query(
+                "INSERT INTO download_files (job_id, position, url, mime, kind, headers, file_name) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(job_id)
+            .bind(position as i64)
+            .bind(&asset.url)
+            .bind(&asset.mime)
+            .bind(asset_kind_to_str(&asset.kind))
+            .bind(serde_json::to_string(&asset.headers)?)
+            .bind(file_name)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(job_id)
+    }
+
+    pub async fn get_download_job(&self, job_id: i64) -> Result<Option<DownloadJob>> {
+        let row = sqlx::query("SELECT * FROM download_jobs WHERE id = ?")
+            .bind(job_id)
+            .fetch_optional(self.pool()?)
+            .await?;
+        row.as_ref().map(job_from_row).transpose()
+    }
+
+    /// The download job of a unit, if one exists
+    pub async fn find_download_job(&self, plugin: &str, unit_id: &str) -> Result<Option<DownloadJob>> {
+        let row = sqlx::query("SELECT * FROM download_jobs WHERE plugin = ? AND unit_id = ?")
+            .bind(plugin)
+            .bind(unit_id)
+            .fetch_optional(self.pool()?)
+            .await?;
+        row.as_ref().map(job_from_row).transpose()
+    }
+
+    /// Download jobs of one media item, oldest first
+    pub async fn media_download_jobs(&self, plugin: &str, media_id: &str) -> Result<Vec<DownloadJob>> {
+        let rows = sqlx::query("SELECT * FROM download_jobs WHERE plugin = ? AND media_id = ? ORDER BY id")
+            .bind(plugin)
+            .bind(media_id)
+            .fetch_all(self.pool()?)
+            .await?;
+        rows.iter().map(job_from_row).collect()
+    }
+
+    /// All download jobs, newest first
+    pub async fn list_download_jobs(&self) -> Result<Vec<DownloadJob>> {
+        let rows = sqlx::query("SELECT * FROM download_jobs ORDER BY created_at DESC, id DESC")
+            .fetch_all(self.pool()?)
+            .await?;
+        rows.iter().map(job_from_row).collect()
+    }
+
+    /// Files of a download job in asset order
+    pub async fn download_files(&self, job_id: i64) -> Result<Vec<DownloadFile>> {
+        let rows = sqlx::query("SELECT * FROM download_files WHERE job_id = ? ORDER BY position")
+            .bind(job_id)
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(rows.iter().map(file_from_row).collect())
+    }
+
+    pub async fn set_download_state(&self, job_id: i64, state: DownloadState, error: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE download_jobs SET state = ?, error = ?, updated_at = ? WHERE id = ?")
+            .bind(state.as_str())
+            .bind(error)
+            .bind(now_secs())
+            .bind(job_id)
+            .execute(self.pool()?)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark a file finished and roll its size into the job totals
+    pub async fn complete_download_file(&self, job_id: i64, position: u32, bytes: u64) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        sqlx::query("UPDATE download_files SET done = 1, bytes = ? WHERE job_id = ? AND position = ? AND done = 0")
+            .bind(bytes as i64)
+            .bind(job_id)
+            .bind(position)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE download_jobs SET
+                completed_files = (SELECT COUNT(*) FROM download_files WHERE job_id = ?1 AND done = 1),
+                bytes = (SELECT COALESCE(SUM(bytes), 0) FROM download_files WHERE job_id = ?1),
+                updated_at = ?2
+             WHERE id = ?1",
+        )
+        .bind(job_id)
+        .bind(now_secs())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Point unfinished files at freshly resolved asset URLs and headers (asset links often expire)
+    pub async fn refresh_download_urls(&self, job_id: i64, assets: &[Asset]) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        for (position, asset) in assets.iter().enumerate() {
+            sqlx::query("UPDATE download_files SET url = ?, headers = ? WHERE job_id = ? AND position = ? AND done = 0")
+                .bind(&asset.url)
+                .bind(serde_json::to_string(&asset.headers)?)
+                .bind(job_id)
+                .bind(position as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn delete_download_job(&self, job_id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM download_jobs WHERE id = ?")
+            .bind(job_id)
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Jobs left queued or running by a previous process are paused so they can be resumed explicitly
+    pub async fn pause_interrupted_downloads(&self) -> Result<u64> {
+        let res = sqlx::query("UPDATE download_jobs SET state = ?, updated_at = ? WHERE state IN (?, ?)")
+            .bind(DownloadState::Paused.as_str())
+            .bind(now_secs())
+            .bind(DownloadState::Queued.as_str())
+            .bind(DownloadState::Running.as_str())
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected())
+    }
+}
+
+fn job_from_row(row: &SqliteRow) -> Result<DownloadJob> {
+    Ok(DownloadJob {
+        id: row.get("id"),
+        plugin: row.get("plugin"),
+        media_id: row.get("media_id"),
+        unit: serde_json::from_str(row.get("unit"))?,
+        state: DownloadState::from_str(row.get("state")),
+        directory: PathBuf::from(row.get::<String, _>("directory")),
+        total_files: row.get("total_files"),
+        completed_files: row.get("completed_files"),
+        bytes: row.get::<i64, _>("bytes").max(0) as u64,
+        error: row.get("error"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
+
+fn file_from_row(row: &SqliteRow) -> DownloadFile {
+    DownloadFile {
+        job_id: row.get("job_id"),
+        position: row.get("position"),
+        url: row.get("url"),
+        mime: row.get("mime"),
+        kind: asset_kind_from_str(row.get("kind")),
+        headers: serde_json::from_str(row.get("headers")).unwrap_or_default(),
+        file_name: row.get("file_name"),
+        done: row.get("done"),
+        bytes: row.get::<i64, _>("bytes").max(0) as u64,
+    }
+}
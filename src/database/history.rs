@@ -0,0 +1,76 @@
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Row, Sqlite};
+
+use crate::database::convert::now_secs;
+use crate::database::Database;
+
+/// A single browsing history record
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub plugin: String,
+    /// None when the unit's media item was not known at the time
+    pub media_id: Option<String>,
+    /// None for media-level views (unit listings)
+    pub unit_id: Option<String>,
+    /// Unix seconds
+    pub viewed_at: i64,
+}
+
+/// Browsing history of viewed media and units
+impl Database {
+    /// Append a history entry, returning its id
+    pub async fn record_history(&self, plugin: &str, media_id: Option<&str>, unit_id: Option<&str>) -> Result<i64> {
+        let res = sqlx::query("INSERT INTO history (plugin, media_id, unit_id, viewed_at) VALUES (?, ?, ?, ?)")
+            .bind(plugin)
+            .bind(media_id)
+            .bind(unit_id)
+            .bind(now_secs())
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    /// Most recent history entries, newest first
+    pub async fn recent_history(&self, limit: u32) -> Result<Vec<HistoryEntry>> {
+        let rows = sqlx::query("SELECT * FROM history ORDER BY viewed_at DESC, id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(rows.iter().map(history_from_row).collect())
+    }
+
+    /// Delete a single history entry. Returns false if it did not exist.
+    pub async fn delete_history_entry(&self, id: i64) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM history WHERE id = ?")
+            .bind(id)
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Delete history viewed within `[from, to)` (unix seconds). Open ends clear everything on that side.
+    /// Returns the number of entries removed.
+    pub async fn clear_history(&self, from: Option<i64>, to: Option<i64>) -> Result<u64> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("DELETE FROM history WHERE 1 = 1");
+        if let Some(from) = from {
+            qb.push(" AND viewed_at >= ").push_bind(from);
+        }
+        if let Some(to) = to {
+            qb.push(" AND viewed_at < ").push_bind(to);
+        }
+        let res = qb.build().execute(self.pool()?).await?;
+        Ok(res.rows_affected())
+    }
+}
+
+fn history_from_row(row: &SqliteRow) -> HistoryEntry {
+    HistoryEntry {
+        id: row.get("id"),
+        plugin: row.get("plugin"),
+        media_id: row.get("media_id"),
+        unit_id: row.get("unit_id"),
+        viewed_at: row.get("viewed_at"),
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::{anyhow, Result};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{QueryBuilder, Row, Sqlite};
+
+use crate::database::convert::*;
+use crate::database::media::{media_from_row, touch_plugin, upsert_media_tx};
+use crate::database::Database;
+use crate::plugins::{Media, MediaType};
+
+/// Separator used when aggregating tags/categories into a single column
+const LIST_SEP: &str = "\u{1f}";
+
+/// A media item saved to the user's library
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    pub plugin: String,
+    pub media: Media,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    /// Unix seconds
+    pub added_at: i64,
+    /// Unix seconds of the last change to the entry's user data
+    pub updated_at: i64,
+}
+
+/// Sort order for library listings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LibrarySort {
+    #[default]
+    AddedAt,
+    UpdatedAt,
+    Title,
+}
+
+/// Filters and ordering for `Database::list_library`
+#[derive(Debug, Clone, Default)]
+pub struct LibraryQuery {
+    pub media_type: Option<MediaType>,
+    /// Only entries in this category
+    pub category: Option<String>,
+    /// Only entries carrying this tag
+    pub tag: Option<String>,
+    pub sort: LibrarySort,
+    pub descending: bool,
+}
+
+/// User library: saved media with categories, tags and notes
+impl Database {
+    /// Save a media item to the library. Stored metadata is refreshed if the entry already exists.
+    pub async fn add_to_library(&self, plugin: &str, media: &Media) -> Result<()> {
+        let now = now_secs();
+        let mut tx = self.pool()?.begin().await?;
+        touch_plugin(&mut tx, plugin).await?;
+        upsert_media_tx(&mut tx, plugin, media).await?;
+        sqlx::query(
+            "INSERT INTO library_entries (plugin, media_id, added_at, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (plugin, media_id) DO NOTHING",
+        )
+        .bind(plugin)
+        .bind(&media.id)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Remove a media item from the library. Returns false if it was not saved.
+    pub async fn remove_from_library(&self, plugin: &str, media_id: &str) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM library_entries WHERE plugin = ? AND media_id = ?")
+            .bind(plugin)
+            .bind(media_id)
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Look up a single library entry
+    pub async fn get_library_entry(&self, plugin: &str, media_id: &str) -> Result<Option<LibraryEntry>> {
+        let mut qb = entry_select();
+        qb.push(" WHERE e.plugin = ").push_bind(plugin);
+        qb.push(" AND e.media_id = ").push_bind(media_id);
+        let row = qb.build().fetch_optional(self.pool()?).await?;
+        Ok(row.as_ref().map(entry_from_row))
+    }
+
+    /// List library entries matching the query
+    pub async fn list_library(&self, query: &LibraryQuery) -> Result<Vec<LibraryEntry>> {
+        let mut qb = entry_select();
+        qb.push(" WHERE 1 = 1");
+        if let Some(kind) = &query.media_type {
+            qb.push(" AND m.mediatype = ").push_bind(media_type_to_str(kind));
+        }
+        if let Some(category) = &query.category {
+            qb.push(
+                " AND EXISTS (SELECT 1 FROM library_categories lc JOIN categories c ON c.id = lc.category_id
+                   WHERE lc.plugin = e.plugin AND lc.media_id = e.media_id AND c.name = ",
+            )
+            .push_bind(category)
+            .push(")");
+        }
+        if let Some(tag) = &query.tag {
+            qb.push(" AND EXISTS (SELECT 1 FROM library_tags t WHERE t.plugin = e.plugin AND t.media_id = e.media_id AND t.tag = ")
+                .push_bind(tag)
+                .push(")");
+        }
+        let column = match query.sort {
+            LibrarySort::AddedAt => "e.added_at",
+            LibrarySort::UpdatedAt => "e.updated_at",
+            LibrarySort::Title => "m.title COLLATE NOCASE",
+        };
+        qb.push(" ORDER BY ").push(column).push(if query.descending { " DESC" } else { " ASC" });
+        let rows = qb.build().fetch_all(self.pool()?).await?;
+        Ok(rows.iter().map(entry_from_row).collect())
+    }
+
+    /// Set or clear the notes of a library entry
+    pub async fn set_library_notes(&self, plugin: &str, media_id: &str, notes: Option<&str>) -> Result<()> {
+        let res = sqlx::query("UPDATE library_entries SET notes = ?, updated_at = ? WHERE plugin = ? AND media_id = ?")
+            .bind(notes)
+            .bind(now_secs())
+            .bind(plugin)
+            .bind(media_id)
+            .execute(self.pool()?)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Err(anyhow!("not in library: {}/{}", plugin, media_id));
+        }
+        Ok(())
+    }
+
+    /// Replace the tags of a library entry
+    pub async fn set_library_tags(&self, plugin: &str, media_id: &str, tags: &[String]) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        let res = sqlx::query("UPDATE library_entries SET updated_at = ? WHERE plugin = ? AND media_id = ?")
+            .bind(now_secs())
+            .bind(plugin)
+            .bind(media_id)
+            .execute(&mut *tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Err(anyhow!("not in library: {}/{}", plugin, media_id));
+        }
+        sqlx::query("DELETE FROM library_tags WHERE plugin = ? AND media_id = ?")
+            .bind(plugin)
+            .bind(media_id)
+            .execute(&mut *tx)
+            .await?;
+        for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            sqlx::query("INSERT OR IGNORE INTO library_tags (plugin, media_id, tag) VALUES (?, ?, ?)")
+                .bind(plugin)
+                .bind(media_id)
+                .bind(tag)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replace the categories of a library entry, creating categories that do not exist yet
+    pub async fn set_library_categories(&self, plugin: &str, media_id: &str, categories: &[String]) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        let res = sqlx::query("UPDATE library_entries SET updated_at = ? WHERE plugin = ? AND media_id = ?")
+            .bind(now_secs())
+            .bind(plugin)
+            .bind(media_id)
+            .execute(&mut *tx)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Err(anyhow!("not in library: {}/{}", plugin, media_id));
+        }
+        sqlx::query("DELETE FROM library_categories WHERE plugin = ? AND media_id = ?")
+            .bind(plugin)
+            .bind(media_id)
+            .execute(&mut *tx)
+            .await?;
+        for name in categories.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            sqlx::query("INSERT OR IGNORE INTO categories (name, position) SELECT ?, COALESCE(MAX(position) + 1, 0) FROM categories")
+                .bind(name)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT OR IGNORE INTO library_categories (plugin, media_id, category_id)
+                 SELECT ?, ?, id FROM categories WHERE name = ?",
+            )
+            .bind(plugin)
+            .bind(media_id)
+            .bind(name)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// All category names in display order
+    pub async fn list_categories(&self) -> Result<Vec<String>> {
+        let names = sqlx::query_scalar("SELECT name FROM categories ORDER BY position, name")
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(names)
+    }
+
+    /// Create a category at the end of the display order. Existing categories are left unchanged.
+    pub async fn create_category(&self, name: &str) -> Result<()> {
+        sqlx::query("INSERT OR IGNORE INTO categories (name, position) SELECT ?, COALESCE(MAX(position) + 1, 0) FROM categories")
+            .bind(name.trim())
+            .execute(self.pool()?)
+            .await?;
+        Ok(())
+    }
+
+    /// Rename a category
+    pub async fn rename_category(&self, name: &str, new_name: &str) -> Result<()> {
+        let res = sqlx::query("UPDATE categories SET name = ? WHERE name = ?")
+            .bind(new_name.trim())
+            .bind(name)
+            .execute(self.pool()?)
+            .await?;
+        if res.rows_affected() == 0 {
+            return Err(anyhow!("category not found: {}", name));
+        }
+        Ok(())
+    }
+
+    /// Delete a category. Entries in it stay in the library.
+    pub async fn delete_category(&self, name: &str) -> Result<bool> {
+        let res = sqlx::query("DELETE FROM categories WHERE name = ?")
+            .bind(name)
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+fn entry_select<'a>() -> QueryBuilder<'a, Sqlite> {
+    QueryBuilder::new(format!(
+        "SELECT m.*, e.plugin AS entry_plugin, e.notes, e.added_at, e.updated_at AS entry_updated_at,
+            (SELECT group_concat(t.tag, '{sep}') FROM library_tags t
+              WHERE t.plugin = e.plugin AND t.media_id = e.media_id) AS tags,
+            (SELECT group_concat(c.name, '{sep}') FROM library_categories lc JOIN categories c ON c.id = lc.category_id
+              WHERE lc.plugin = e.plugin AND lc.media_id = e.media_id) AS categories
+         FROM library_entries e JOIN media m ON m.plugin = e.plugin AND m.id = e.media_id",
+        sep = LIST_SEP
+    ))
+}
+
+fn entry_from_row(row: &SqliteRow) -> LibraryEntry {
+    let split = |col: &str| -> Vec<String> {
+        row.get::<Option<String>, _>(col)
+            .map(|s| s.split(LIST_SEP).map(str::to_string).collect())
+            .unwrap_or_default()
+    };
+    let mut tags = split("tags");
+    tags.sort();
+    LibraryEntry {
+        plugin: row.get("entry_plugin"),
+        media: media_from_row(row),
+        categories: split("categories"),
+        tags,
+        notes: row.get("notes"),
+        added_at: row.get("added_at"),
+        updated_at: row.get("entry_updated_at"),
+    }
+}
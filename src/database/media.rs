@@ -0,0 +1,273 @@
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, Sqlite, Transaction};
+
+use crate::database::convert::*;
+use crate::database::Database;
+use crate::plugins::{Asset, Media, MediaDetails, Unit};
+
+/// Persistence of provider records (media, units, assets), keyed by the plugin they came from
+impl Database {
+    /// Insert or update a media record from the given plugin
+    pub async fn upsert_media(&self, plugin: &str, media: &Media) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        touch_plugin(&mut tx, plugin).await?;
+        upsert_media_tx(&mut tx, plugin, media).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Look up a stored media record
+    pub async fn get_media(&self, plugin: &str, media_id: &str) -> Result<Option<Media>> {
+        let row = sqlx::query("SELECT * FROM media WHERE plugin = ? AND id = ?")
+            .bind(plugin)
+            .bind(media_id)
+            .fetch_optional(self.pool()?)
+            .await?;
+        Ok(row.as_ref().map(media_from_row))
+    }
+
+    /// Insert or update the extended metadata of a media item, together with its media record
+    pub async fn upsert_media_details(&self, plugin: &str, details: &MediaDetails) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        touch_plugin(&mut tx, plugin).await?;
+        upsert_media_tx(&mut tx, plugin, &details.media).await?;
+        sqlx::query(
+            "INSERT INTO media_details (plugin, media_id, status, content_rating, rating, year, banner_url, credits, tags, alt_titles, external_ids, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT (plugin, media_id) DO UPDATE SET
+                status = excluded.status, content_rating = excluded.content_rating, rating = excluded.rating,
+                year = excluded.year, banner_url = excluded.banner_url, credits = excluded.credits, tags = excluded.tags,
+                alt_titles = excluded.alt_titles, external_ids = excluded.external_ids, updated_at = excluded.updated_at",
+        )
+        .bind(plugin)
+        .bind(&details.media.id)
+        .bind(details.status.map(publication_status_to_str))
+        .bind(details.content_rating.map(content_rating_to_str))
+        .bind(details.rating)
+        .bind(details.year)
+        .bind(&details.banner_url)
+        .bind(serde_json::to_string(&details.credits)?)
+        .bind(serde_json::to_string(&details.tags)?)
+        .bind(serde_json::to_string(&details.alt_titles)?)
+        .bind(serde_json::to_string(&details.external_ids)?)
+        .bind(now_secs())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Look up the stored extended metadata of a media item
+    pub async fn get_media_details(&self, plugin: &str, media_id: &str) -> Result<Option<MediaDetails>> {
+        let row = sqlx::query(
+            "SELECT m.*, d.status, d.content_rating, d.rating, d.year, d.banner_url, d.credits, d.tags, d.alt_titles, d.external_ids
+             FROM media_details d JOIN media m ON m.plugin = d.plugin AND m.id = d.media_id
+             WHERE d.plugin = ? AND d.media_id = ?",
+        )
+        .bind(plugin)
+        .bind(media_id)
+        .fetch_optional(self.pool()?)
+        .await?;
+        row.as_ref().map(media_details_from_row).transpose()
+    }
+
+    /// Replace the stored unit list of a media item. The media record must already be stored.
+    pub async fn replace_units(&self, plugin: &str, media_id: &str, units: &[Unit]) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        let now = now_secs();
+        let keep: Vec<&str> = units.iter().map(|u| u.id.as_str()).collect();
+        let existing: Vec<String> = sqlx::query_scalar("SELECT id FROM units WHERE plugin = ? AND media_id = ?")
+            .bind(plugin)
+            .bind(media_id)
+            .fetch_all(&mut *tx)
+            .await?;
+        for id in existing.iter().filter(|id| !keep.contains(&id.as_str())) {
+            sqlx::query("DELETE FROM units WHERE plugin = ? AND id = ?")
+                .bind(plugin)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for (position, unit) in units.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO units (plugin, media_id, id, title, number_text, number, lang, unit_group, url, published_at, kind, upload_group, position, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                 ON CONFLICT (plugin, id) DO UPDATE SET
+                    media_id = excluded.media_id, title = excluded.title, number_text = excluded.number_text,
+                    number = excluded.number, lang = excluded.lang, unit_group = excluded.unit_group, url = excluded.url,
+                    published_at = excluded.published_at, kind = excluded.kind, upload_group = excluded.upload_group,
+                    position = excluded.position, updated_at = excluded.updated_at",
+            )
+            .bind(plugin)
+            .bind(media_id)
+            .bind(&unit.id)
+            .bind(&unit.title)
+            .bind(&unit.number_text)
+            .bind(unit.number)
+            .bind(&unit.lang)
+            .bind(&unit.group)
+            .bind(&unit.url)
+            .bind(&unit.published_at)
+            .bind(unit_kind_to_str(&unit.kind))
+            .bind(&unit.upload_group)
+            .bind(position as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stored units of a media item, in the order the plugin listed them
+    pub async fn get_units(&self, plugin: &str, media_id: &str) -> Result<Vec<Unit>> {
+        let rows = sqlx::query("SELECT * FROM units WHERE plugin = ? AND media_id = ? ORDER BY position")
+            .bind(plugin)
+            .bind(media_id)
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(rows.iter().map(unit_from_row).collect())
+    }
+
+    /// The media item a stored unit belongs to
+    pub async fn unit_media_id(&self, plugin: &str, unit_id: &str) -> Result<Option<String>> {
+        let media_id = sqlx::query_scalar("SELECT media_id FROM units WHERE plugin = ? AND id = ?")
+            .bind(plugin)
+            .bind(unit_id)
+            .fetch_optional(self.pool()?)
+            .await?;
+        Ok(media_id)
+    }
+
+    /// Replace the stored assets of a unit. The unit must already be stored.
+    pub async fn replace_assets(&self, plugin: &str, unit_id: &str, assets: &[Asset]) -> Result<()> {
+        let mut tx = self.pool()?.begin().await?;
+        let now = now_secs();
+        sqlx::query("DELETE FROM assets WHERE plugin = ? AND unit_id = ?")
+            .bind(plugin)
+            .bind(unit_id)
+            .execute(&mut *tx)
+            .await?;
+        for (position, asset) in assets.iter().enumerate() {
+            sqlx::query(
+                "INSERT INTO assets (plugin, unit_id, position, url, mime, width, height, kind, headers, expires_at, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(plugin)
+            .bind(unit_id)
+            .bind(position as i64)
+            .bind(&asset.url)
+            .bind(&asset.mime)
+            .bind(asset.width)
+            .bind(asset.height)
+            .bind(asset_kind_to_str(&asset.kind))
+            .bind(serde_json::to_string(&asset.headers)?)
+            .bind(asset.expires_at.map(|at| at as i64))
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stored assets of a unit, in order
+    pub async fn get_assets(&self, plugin: &str, unit_id: &str) -> Result<Vec<Asset>> {
+        let rows = sqlx::query("SELECT * FROM assets WHERE plugin = ? AND unit_id = ? ORDER BY position")
+            .bind(plugin)
+            .bind(unit_id)
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(rows.iter().map(asset_from_row).collect())
+    }
+}
+
+/// Record that a plugin has contributed data
+pub(crate) async fn touch_plugin(tx: &mut Transaction<'_, Sqlite>, plugin: &str) -> Result<()> {
+    let now = now_secs();
+    sqlx::query(
+        "INSERT INTO plugins (name, first_seen_at, last_seen_at) VALUES (?, ?, ?)
+         ON CONFLICT (name) DO UPDATE SET last_seen_at = excluded.last_seen_at",
+    )
+    .bind(plugin)
+    .bind(now)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub(crate) async fn upsert_media_tx(tx: &mut Transaction<'_, Sqlite>, plugin: &str, media: &Media) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO media (plugin, id, mediatype, title, description, url, cover_url, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (plugin, id) DO UPDATE SET
+            mediatype = excluded.mediatype, title = excluded.title, description = excluded.description,
+            url = excluded.url, cover_url = excluded.cover_url, updated_at = excluded.updated_at",
+    )
+    .bind(plugin)
+    .bind(&media.id)
+    .bind(media_type_to_str(&media.mediatype))
+    .bind(&media.title)
+    .bind(&media.description)
+    .bind(&media.url)
+    .bind(&media.cover_url)
+    .bind(now_secs())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub(crate) fn media_from_row(row: &SqliteRow) -> Media {
+    Media {
+        id: row.get("id"),
+        mediatype: media_type_from_str(row.get("mediatype")),
+        title: row.get("title"),
+        description: row.get("description"),
+        url: row.get("url"),
+        cover_url: row.get("cover_url"),
+    }
+}
+
+fn media_details_from_row(row: &SqliteRow) -> Result<MediaDetails> {
+    Ok(MediaDetails {
+        media: media_from_row(row),
+        credits: serde_json::from_str(row.get("credits"))?,
+        tags: serde_json::from_str(row.get("tags"))?,
+        status: row.get::<Option<&str>, _>("status").and_then(publication_status_from_str),
+        alt_titles: serde_json::from_str(row.get("alt_titles"))?,
+        content_rating: row.get::<Option<&str>, _>("content_rating").and_then(content_rating_from_str),
+        rating: row.get("rating"),
+        year: row.get("year"),
+        banner_url: row.get("banner_url"),
+        external_ids: serde_json::from_str(row.get("external_ids"))?,
+    })
+}
+
+pub(crate) fn unit_from_row(row: &SqliteRow) -> Unit {
+    Unit {
+        id: row.get("id"),
+        title: row.get("title"),
+        number_text: row.get("number_text"),
+        number: row.get("number"),
+        lang: row.get("lang"),
+        group: row.get("unit_group"),
+        url: row.get("url"),
+        published_at: row.get("published_at"),
+        kind: unit_kind_from_str(row.get("kind")),
+        upload_group: row.get("upload_group"),
+    }
+}
+
+pub(crate) fn asset_from_row(row: &SqliteRow) -> Asset {
+    Asset {
+        url: row.get("url"),
+        mime: row.get("mime"),
+        width: row.get("width"),
+        height: row.get("height"),
+        kind: asset_kind_from_str(row.get("kind")),
+        headers: serde_json::from_str(row.get("headers")).unwrap_or_default(),
+        expires_at: row.get::<Option<i64>, _>("expires_at").map(|at| at.max(0) as u64),
+    }
+}
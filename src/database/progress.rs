@@ -0,0 +1,287 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+
+use crate::database::convert::now_secs;
+use crate::database::Database;
+use crate::plugins::Unit;
+
+/// Consumption state of a single unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitState {
+    Unread,
+    InProgress,
+    Completed,
+}
+
+impl UnitState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            UnitState::Unread => "unread",
+            UnitState::InProgress => "in_progress",
+            UnitState::Completed => "completed",
+        }
+    }
+
+    fn from_str(s: &str) -> Self {
+        match s {
+            "in_progress" => UnitState::InProgress,
+            "completed" => UnitState::Completed,
+            _ => UnitState::Unread,
+        }
+    }
+}
+
+/// Stored progress of a unit
+#[derive(Debug, Clone)]
+pub struct UnitProgress {
+    pub plugin: String,
+    pub media_id: String,
+    pub unit_id: String,
+    pub state: UnitState,
+    /// Page index for paged units
+    pub page: Option<u32>,
+    /// Playback position for audio/video units
+    pub position: Option<Duration>,
+    /// Unix seconds
+    pub updated_at: i64,
+}
+
+/// Where to pick a media item back up
+#[derive(Debug, Clone)]
+pub struct ResumeInfo {
+    pub plugin: String,
+    pub media_id: String,
+    /// Most recently touched unit, if any
+    pub last: Option<UnitProgress>,
+    /// Unit to continue with: the in-progress unit, or the first unread unit after the furthest completed one
+    pub next: Option<Unit>,
+    /// Progress recorded for `next`, when it is partially consumed
+    pub next_progress: Option<UnitProgress>,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Reading/watching progress per unit
+impl Database {
+    /// Record progress for a unit. `page` and `position` are kept as given, so pass the latest known values.
+    pub async fn set_unit_progress(
+        &self,
+        plugin: &str,
+        media_id: &str,
+        unit_id: &str,
+        state: UnitState,
+        page: Option<u32>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO unit_progress (plugin, unit_id, media_id, state, page, position_ms, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT (plugin, unit_id) DO UPDATE SET
+                media_id = excluded.media_id, state = excluded.state, page = excluded.page,
+                position_ms = excluded.position_ms, updated_at = excluded.updated_at",
+        )
+        .bind(plugin)
+        .bind(unit_id)
+        .bind(media_id)
+        .bind(state.as_str())
+        .bind(page)
+        .bind(position.map(|p| p.as_millis() as i64))
+        .bind(now_secs())
+        .execute(self.pool()?)
+        .await?;
+        Ok(())
+    }
+
+    /// Forget progress for a unit, returning it to unread
+    pub async fn clear_unit_progress(&self, plugin: &str, unit_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM unit_progress WHERE plugin = ? AND unit_id = ?")
+            .bind(plugin)
+            .bind(unit_id)
+            .execute(self.pool()?)
+            .await?;
+        Ok(())
+    }
+
+    /// Progress of a single unit
+    pub async fn get_unit_progress(&self, plugin: &str, unit_id: &str) -> Result<Option<UnitProgress>> {
+        let row = sqlx::query("SELECT * FROM unit_progress WHERE plugin = ? AND unit_id = ?")
+            .bind(plugin)
+            .bind(unit_id)
+            .fetch_optional(self.pool()?)
+            .await?;
+        Ok(row.as_ref().map(progress_from_row))
+    }
+
+    /// All recorded progress for a media item, most recent first
+    pub async fn list_unit_progress(&self, plugin: &str, media_id: &str) -> Result<Vec<UnitProgress>> {
+        let rows = sqlx::query("SELECT * FROM unit_progress WHERE plugin = ? AND media_id = ? ORDER BY updated_at DESC")
+            .bind(plugin)
+            .bind(media_id)
+            .fetch_all(self.pool()?)
+            .await?;
+        Ok(rows.iter().map(progress_from_row).collect())
+    }
+
+    /// Resume information for a media item, based on its stored units
+    pub async fn resume_info(&self, plugin: &str, media_id: &str) -> Result<ResumeInfo> {
+        let units = self.get_units(plugin, media_id).await?;
+        let progress = self.list_unit_progress(plugin, media_id).await?;
+        Ok(build_resume(plugin, media_id, units, progress))
+    }
+
+    /// Resume information for every library entry, most recently read first
+    pub async fn library_resume(&self) -> Result<Vec<ResumeInfo>> {
+        let keys: Vec<(String, String)> = sqlx::query_as("SELECT plugin, media_id FROM library_entries")
+            .fetch_all(self.pool()?)
+            .await?;
+        let mut out = Vec::with_capacity(keys.len());
+        for (plugin, media_id) in keys {
+            out.push(self.resume_info(&plugin, &media_id).await?);
+        }
+        out.sort_by_key(|r| std::cmp::Reverse(r.last.as_ref().map(|p| p.updated_at).unwrap_or(0)));
+        Ok(out)
+    }
+}
+
+fn progress_from_row(row: &SqliteRow) -> UnitProgress {
+    UnitProgress {
+        plugin: row.get("plugin"),
+        media_id: row.get("media_id"),
+        unit_id: row.get("unit_id"),
+        state: UnitState::from_str(row.get("state")),
+        page: row.get("page"),
+        position: row.get::<Option<i64>, _>("position_ms").map(|ms| Duration::from_millis(ms.max(0) as u64)),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn build_resume(plugin: &str, media_id: &str, units: Vec<Unit>, progress: Vec<UnitProgress>) -> ResumeInfo {
+    let last = progress.first().cloned();
+    let by_unit: HashMap<&str, &UnitProgress> = progress.iter().map(|p| (p.unit_id.as_str(), p)).collect();
+    let ordered = reading_order(units);
+    let completed = ordered
+        .iter()
+        .filter(|u| matches!(by_unit.get(u.id.as_str()), Some(p) if p.state == UnitState::Completed))
+        .count();
+    let total = ordered.len();
+
+    // A partially consumed unit always wins; progress is ordered most recent first
+    let in_progress = progress
+        .iter()
+        .filter(|p| p.state == UnitState::InProgress)
+        .find_map(|p| ordered.iter().find(|u| u.id == p.unit_id).map(|u| (u.clone(), p.clone())));
+    let (next, next_progress) = match in_progress {
+        Some((unit, p)) => (Some(unit), Some(p)),
+        None => (next_after_completed(&ordered, &by_unit), None),
+    };
+
+    ResumeInfo { plugin: plugin.to_string(), media_id: media_id.to_string(), last, next, next_progress, completed, total }
+}
+
+/// First unit after the furthest completed one, skipping alternate releases of completed numbers
+fn next_after_completed(ordered: &[Unit], by_unit: &HashMap<&str, &UnitProgress>) -> Option<Unit> {
+    let is_done = |u: &Unit| matches!(by_unit.get(u.id.as_str()), Some(p) if p.state == UnitState::Completed);
+    let done_numbers: Vec<f32> = ordered.iter().filter(|u| is_done(u)).filter_map(|u| u.number).collect();
+    let start = ordered.iter().rposition(is_done).map(|i| i + 1).unwrap_or(0);
+    ordered[start..]
+        .iter()
+        .find(|u| !is_done(u) && !matches!(u.number, Some(n) if done_numbers.contains(&n)))
+        .cloned()
+}
+
+/// Sort units into consumption order: by group (volume/season) ordinal, then by unit number.
+/// Ungrouped units go after grouped ones, since sources usually leave the newest units ungrouped.
+/// Units without a number keep their listed order relative to each other.
+fn reading_order(units: Vec<Unit>) -> Vec<Unit> {
+    let mut indexed: Vec<(usize, Unit)> = units.into_iter().enumerate().collect();
+    indexed.sort_by(|(ia, a), (ib, b)| {
+        let (ga, gb) = (group_key(a.group.as_deref()), group_key(b.group.as_deref()));
+        ga.0.cmp(&gb.0)
+            .then_with(|| ga.1.total_cmp(&gb.1))
+            .then_with(|| match (a.number, b.number) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+            .then_with(|| ia.cmp(ib))
+    });
+    indexed.into_iter().map(|(_, u)| u).collect()
+}
+
+/// Orderable key for a group label such as "Volume 3" or "Season 2"
+fn group_key(group: Option<&str>) -> (u8, f32) {
+    let Some(label) = group else {
+        return (1, 0.0);
+    };
+    let digits: String = label
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    (0, digits.parse().unwrap_or(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::UnitKind;
+
+    fn unit(id: &str, number: Option<f32>, group: Option<&str>) -> Unit {
+        Unit {
+            id: id.to_string(),
+            title: id.to_string(),
+            number_text: None,
+            number,
+            lang: None,
+            group: group.map(str::to_string),
+            url: None,
+            published_at: None,
+            kind: UnitKind::Chapter,
+            upload_group: None,
+        }
+    }
+
+    fn ids(units: &[Unit]) -> Vec<&str> {
+        units.iter().map(|u| u.id.as_str()).collect()
+    }
+
+    #[test]
+    fn group_key_parses_labels() {
+        assert_eq!(group_key(Some("Volume 3")), (0, 3.0));
+        assert_eq!(group_key(Some("Season 2.5 (final)")), (0, 2.5));
+        assert_eq!(group_key(Some("Vol.10")), (0, 10.0));
+        assert_eq!(group_key(Some("Extras")), (0, 0.0));
+        assert_eq!(group_key(None), (1, 0.0));
+    }
+
+    #[test]
+    fn orders_by_group_then_number_with_ungrouped_last() {
+        let units = vec![
+            unit("new", Some(21.0), None),
+            unit("v2c11", Some(11.0), Some("Volume 2")),
+            unit("v1c2", Some(2.0), Some("Volume 1")),
+            unit("v10c90", Some(90.0), Some("Volume 10")),
+            unit("v1c1", Some(1.0), Some("Volume 1")),
+        ];
+        assert_eq!(ids(&reading_order(units)), ["v1c1", "v1c2", "v2c11", "v10c90", "new"]);
+    }
+
+    #[test]
+    fn unnumbered_units_keep_listed_order_after_numbered() {
+        let units = vec![unit("x", None, None), unit("b", Some(2.0), None), unit("y", None, None), unit("a", Some(1.0), None)];
+        assert_eq!(ids(&reading_order(units)), ["a", "b", "x", "y"]);
+    }
+
+    #[test]
+    fn nan_numbers_do_not_break_ordering() {
+        let units = vec![unit("nan", Some(f32::NAN), None), unit("b", Some(2.0), None), unit("a", Some(1.0), None)];
+        let ordered = reading_order(units);
+        assert_eq!(ids(&ordered)[..2], ["a", "b"]);
+        assert_eq!(ordered.len(), 3);
+    }
+}
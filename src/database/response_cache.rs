@@ -0,0 +1,77 @@
+use anyhow::Result;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use crate::database::convert::now_secs;
+use crate::database::Database;
+
+/// A cached plugin response
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// JSON encoded response
+    pub payload: String,
+    /// Unix seconds
+    pub fetched_at: i64,
+}
+
+/// Raw storage for the Aggregator's response cache
+impl Database {
+    /// Look up a cached response
+    pub async fn cache_get(&self, plugin: &str, op: &str, key: &str) -> Result<Option<CachedResponse>> {
+        let row: Option<(String, i64)> = sqlx::query_as(
+            "SELECT payload, fetched_at FROM response_cache WHERE plugin = ? AND op = ? AND key = ?",
+        )
+        .bind(plugin)
+        .bind(op)
+        .bind(key)
+        .fetch_optional(self.pool()?)
+        .await?;
+        Ok(row.map(|(payload, fetched_at)| CachedResponse { payload, fetched_at }))
+    }
+
+    /// Store a response, replacing any previous one
+    pub async fn cache_put(&self, plugin: &str, op: &str, key: &str, payload: &str) -> Result<()> {
+        cache_put(self.pool()?, plugin, op, key, payload).await
+    }
+
+    /// Drop cached responses. Each `None` matches everything, so `(None, None, None)` clears the cache.
+    /// Returns the number of responses removed.
+    pub async fn cache_invalidate(&self, plugin: Option<&str>, op: Option<&str>, key: Option<&str>) -> Result<u64> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("DELETE FROM response_cache WHERE 1 = 1");
+        if let Some(plugin) = plugin {
+            qb.push(" AND plugin = ").push_bind(plugin);
+        }
+        if let Some(op) = op {
+            qb.push(" AND op = ").push_bind(op);
+        }
+        if let Some(key) = key {
+            qb.push(" AND key = ").push_bind(key);
+        }
+        let res = qb.build().execute(self.pool()?).await?;
+        Ok(res.rows_affected())
+    }
+
+    /// Remove responses fetched before the given time (unix seconds)
+    pub async fn cache_prune(&self, older_than: i64) -> Result<u64> {
+        let res = sqlx::query("DELETE FROM response_cache WHERE fetched_at < ?")
+            .bind(older_than)
+            .execute(self.pool()?)
+            .await?;
+        Ok(res.rows_affected())
+    }
+}
+
+/// Pool-level put, used by background revalidation which cannot borrow the Database
+pub(crate) async fn cache_put(pool: &SqlitePool, plugin: &str, op: &str, key: &str, payload: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO response_cache (plugin, op, key, payload, fetched_at) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (plugin, op, key) DO UPDATE SET payload = excluded.payload, fetched_at = excluded.fetched_at",
+    )
+    .bind(plugin)
+    .bind(op)
+    .bind(key)
+    .bind(payload)
+    .bind(now_secs())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,687 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::stream::{self, StreamExt};
+use reqwest::header::{CONTENT_RANGE, LOCATION, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, watch, Semaphore};
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::database::{Database, DownloadFile, DownloadJob, DownloadState};
+use crate::plugins::{apply_headers, Asset, AssetHeader, AssetTransform, HostPolicy, Unit, MAX_TRANSFORM_BYTES};
+
+/// Redirects followed per request; each hop is checked against the plugin's allowed hosts
+const MAX_REDIRECTS: usize = 5;
+/// Buffered events per subscriber before slow receivers start lagging
+const EVENT_CAPACITY: usize = 256;
+/// Longest sanitized path component before it is shortened
+const MAX_COMPONENT_LEN: usize = 64;
+
+/// Tuning for the download manager
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    /// Files transferred at once across all jobs
+    pub max_concurrent_files: usize,
+    /// Minimum delay between two requests of the same job
+    pub request_interval: Duration,
+    /// Overall bandwidth cap in bytes per second, unlimited when None
+    pub max_bytes_per_sec: Option<u64>,
+    /// Attempts per file before the job is marked failed
+    pub max_attempts: u32,
+    pub connect_timeout: Duration,
+    /// Longest wait for the next chunk of a response body
+    pub read_timeout: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent_files: 4,
+            request_interval: Duration::from_millis(250),
+            max_bytes_per_sec: None,
+            max_attempts: 3,
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Progress notifications broadcast to `DownloadManager::subscribe` receivers
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadEvent {
+    Started { job_id: i64 },
+    /// Sent after each finished file
+    Progress { job_id: i64, completed_files: u32, total_files: u32, bytes: u64 },
+    Paused { job_id: i64 },
+    Completed { job_id: i64 },
+    Failed { job_id: i64, error: String },
+    Cancelled { job_id: i64 },
+}
+
+/// Signal sent to a running job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// Spaces out work so that each unit of cost takes at least its share of time
+struct Pacer {
+    next: tokio::sync::Mutex<Instant>,
+}
+
+impl Pacer {
+    fn new() -> Self {
+        Self { next: tokio::sync::Mutex::new(Instant::now()) }
+    }
+
+    async fn wait(&self, cost: Duration) {
+        let start = {
+            let mut next = self.next.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + cost;
+            start
+        };
+        tokio::time::sleep_until(start.into()).await;
+    }
+}
+
+/// Downloads unit assets to disk for offline use. Jobs and their files are tracked in the database,
+/// so interrupted downloads pick up where they left off, including partially transferred files.
+pub struct DownloadManager {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    db: Arc<Database>,
+    root: PathBuf,
+    client: reqwest::Client,
+    options: DownloadOptions,
+    /// Bounds concurrent transfers across all jobs
+    slots: Semaphore,
+    bandwidth: Pacer,
+    events: broadcast::Sender<DownloadEvent>,
+    active: Mutex<HashMap<i64, watch::Sender<Control>>>,
+}
+
+impl DownloadManager {
+    /// Create a manager storing downloads under `root`
+    pub fn new(db: Arc<Database>, root: &Path, options: DownloadOptions) -> Result<Self> {
+        std::fs::create_dir_all(root)
+            .with_context(|| format!("failed to create downloads directory {}", root.display()))?;
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("awasmlib/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(options.connect_timeout)
+            .read_timeout(options.read_timeout)
+            .build()?;
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        Ok(Self {
+            inner: Arc::new(Inner {
+                db,
+                root: root.to_path_buf(),
+                client,
+                slots: Semaphore::new(options.max_concurrent_files.max(1)),
+                options,
+                bandwidth: Pacer::new(),
+                events,
+                active: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Directory downloads are stored under
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Receive progress events for all jobs
+    pub fn subscribe(&self) -> broadcast::Receiver<DownloadEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Whether the job is currently transferring
+    pub fn is_active(&self, job_id: i64) -> bool {
+        self.inner.active.lock().unwrap().contains_key(&job_id)
+    }
+
+    /// Create a job for the resolved assets of a unit and start it. Every asset must be reachable under `policy`.
+    /// An unfinished earlier job for the same unit is replaced, keeping the files it already fetched.
+    /// Fetched images are passed through `transform` before they are stored.
+    pub(crate) async fn enqueue(
+        &self,
+        plugin: &str,
+        media_id: &str,
+        unit: &Unit,
+        assets: &[Asset],
+        policy: HostPolicy,
+        transform: Option<AssetTransform>,
+    ) -> Result<i64> {
+        if assets.is_empty() {
+            bail!("unit {} of plugin {} has no assets to download", unit.id, plugin);
+        }
+        if let Some(asset) = assets.iter().find(|a| !url_permitted(&policy, &a.url)) {
+            bail!("asset {} is not within the allowed hosts of plugin {}", asset.url, plugin);
+        }
+        if let Some(existing) = self.inner.db.find_download_job(plugin, &unit.id).await? {
+            if existing.state == DownloadState::Completed || self.is_active(existing.id) {
+                return Ok(existing.id);
+            }
+            self.inner.db.delete_download_job(existing.id).await?;
+        }
+
+        let directory = self.inner.root
+            .join(path_component(plugin))
+            .join(path_component(media_id))
+            .join(path_component(&unit.id));
+        let file_names: Vec<String> = assets.iter().enumerate()
+            .map(|(i, a)| format!("{:04}.{}", i + 1, file_extension(a)))
+            .collect();
+        let job_id = self.inner.db
+            .create_download_job(plugin, media_id, unit, &directory, assets, &file_names)
+            .await?;
+        info!(plugin=%plugin, unit=%unit.id, job=job_id, files=assets.len(), "queued download");
+        self.start(job_id, policy, transform)?;
+        Ok(job_id)
+    }
+
+    /// Run a stored job in the background. Files already marked done are skipped.
+    pub(crate) fn start(&self, job_id: i64, policy: HostPolicy, transform: Option<AssetTransform>) -> Result<()> {
+        let control = {
+            let mut active = self.inner.active.lock().unwrap();
+            if active.contains_key(&job_id) {
+                return Ok(());
+            }
+            let (tx, rx) = watch::channel(Control::Run);
+            active.insert(job_id, tx);
+            rx
+        };
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            inner.run_job(job_id, policy, transform, control).await;
+            inner.active.lock().unwrap().remove(&job_id);
+        });
+        Ok(())
+    }
+
+    /// Stop a job, keeping its progress so it can be resumed later
+    pub async fn pause(&self, job_id: i64) -> Result<()> {
+        if self.signal(job_id, Control::Pause) {
+            return Ok(());
+        }
+        let job = self.job(job_id).await?;
+        if job.state == DownloadState::Queued {
+            self.inner.db.set_download_state(job_id, DownloadState::Paused, None).await?;
+            let _ = self.inner.events.send(DownloadEvent::Paused { job_id });
+        }
+        Ok(())
+    }
+
+    /// Stop an unfinished job and delete its files. The job stays listed as cancelled.
+    pub async fn cancel(&self, job_id: i64) -> Result<()> {
+        if self.signal(job_id, Control::Cancel) {
+            return Ok(());
+        }
+        let job = self.job(job_id).await?;
+        if !matches!(job.state, DownloadState::Cancelled | DownloadState::Completed) {
+            remove_dir(&job.directory).await;
+            self.inner.db.set_download_state(job_id, DownloadState::Cancelled, None).await?;
+            let _ = self.inner.events.send(DownloadEvent::Cancelled { job_id });
+        }
+        Ok(())
+    }
+
+    /// Forget a job that is not running, deleting its files
+    pub async fn delete(&self, job_id: i64) -> Result<()> {
+        if self.is_active(job_id) {
+            bail!("download job {} is still running", job_id);
+        }
+        let job = self.job(job_id).await?;
+        remove_dir(&job.directory).await;
+        self.inner.db.delete_download_job(job_id).await?;
+        Ok(())
+    }
+
+    async fn job(&self, job_id: i64) -> Result<DownloadJob> {
+        self.inner.db.get_download_job(job_id).await?
+            .ok_or_else(|| anyhow!("download job not found: {}", job_id))
+    }
+
+    /// Send a control signal to a running job; false if the job is not running
+    fn signal(&self, job_id: i64, control: Control) -> bool {
+        match self.inner.active.lock().unwrap().get(&job_id) {
+            Some(tx) => tx.send(control).is_ok(),
+            None => false,
+        }
+    }
+}
+
+impl Inner {
+    async fn run_job(&self, job_id: i64, policy: HostPolicy, transform: Option<AssetTransform>, control: watch::Receiver<Control>) {
+        let outcome = self.transfer_job(job_id, &policy, transform.as_ref(), &control).await;
+        let signal = *control.borrow();
+        let (state, error, event) = match (signal, outcome) {
+            (Control::Cancel, _) => {
+                if let Ok(Some(job)) = self.db.get_download_job(job_id).await {
+                    remove_dir(&job.directory).await;
+                }
+                (DownloadState::Cancelled, None, DownloadEvent::Cancelled { job_id })
+            }
+            (Control::Pause, _) => (DownloadState::Paused, None, DownloadEvent::Paused { job_id }),
+            (Control::Run, Ok(())) => (DownloadState::Completed, None, DownloadEvent::Completed { job_id }),
+            (Control::Run, Err(e)) => {
+                let error = format!("{:#}", e);
+                warn!(job=job_id, error=%error, "download failed");
+                (DownloadState::Failed, Some(error.clone()), DownloadEvent::Failed { job_id, error })
+            }
+        };
+        if let Err(e) = self.db.set_download_state(job_id, state, error.as_deref()).await {
+            warn!(job=job_id, error=%e, "failed to record download state");
+        }
+        debug!(job=job_id, state=state.as_str(), "download finished");
+        let _ = self.events.send(event);
+    }
+
+    /// Fetch every unfinished file of a job; stops at the first failure or control signal
+    async fn transfer_job(
+        &self,
+        job_id: i64,
+        policy: &HostPolicy,
+        transform: Option<&AssetTransform>,
+        control: &watch::Receiver<Control>,
+    ) -> Result<()> {
+        let job = self.db.get_download_job(job_id).await?
+            .ok_or_else(|| anyhow!("download job not found: {}", job_id))?;
+        tokio::fs::create_dir_all(&job.directory).await
+            .with_context(|| format!("failed to create {}", job.directory.display()))?;
+        self.db.set_download_state(job_id, DownloadState::Running, None).await?;
+        let _ = self.events.send(DownloadEvent::Started { job_id });
+
+        let pending: Vec<DownloadFile> = self.db.download_files(job_id).await?
+            .into_iter()
+            .filter(|f| !f.done)
+            .collect();
+        let pacer = Pacer::new();
+        let mut transfers = stream::iter(pending)
+            .map(|file| {
+                let (job, pacer) = (&job, &pacer);
+                let mut control = control.clone();
+                async move {
+                    tokio::select! {
+                        res = self.fetch_file(job, &file, policy, transform, pacer) => res.map(|bytes| (file.position, bytes)),
+                        _ = control.wait_for(|c| *c != Control::Run) => Err(anyhow!("download interrupted")),
+                    }
+                }
+            })
+            .buffer_unordered(self.options.max_concurrent_files.max(1));
+
+        while let Some(res) = transfers.next().await {
+            let (position, bytes) = res?;
+            self.db.complete_download_file(job_id, position, bytes).await?;
+            if let Some(job) = self.db.get_download_job(job_id).await? {
+                let _ = self.events.send(DownloadEvent::Progress {
+                    job_id,
+                    completed_files: job.completed_files,
+                    total_files: job.total_files,
+                    bytes: job.bytes,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Download one file, retrying transient failures. Returns the final file size.
+    async fn fetch_file(
+        &self,
+        job: &DownloadJob,
+        file: &DownloadFile,
+        policy: &HostPolicy,
+        transform: Option<&AssetTransform>,
+        pacer: &Pacer,
+    ) -> Result<u64> {
+        let _slot = self.slots.acquire().await?;
+        let path = file.path(job);
+        let part = job.directory.join(format!("{}.part", file.file_name));
+        let mut attempt = 1;
+        loop {
+            match self.transfer(&file.url, &file.headers, &part, policy, pacer).await {
+                Ok(bytes) => {
+                    return match transform {
+                        // Only images are transformed; anything else is moved into place without being read
+                        Some(transform) if file_asset(file).is_image() => transform_file(transform, file, &part, &path).await,
+                        _ => {
+                            tokio::fs::rename(&part, &path).await
+                                .with_context(|| format!("failed to move download into {}", path.display()))?;
+                            Ok(bytes)
+                        }
+                    };
+                }
+                Err(e) if attempt < self.options.max_attempts && is_transient(&e) => {
+                    debug!(job=job.id, file=file.position, attempt, error=%e, "retrying download");
+                    tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.context(format!("failed to download {}", file.url))),
+            }
+        }
+    }
+
+    /// Fetch `url` into `part` with the asset's required headers, continuing from the part's current length
+    /// when the server supports ranges
+    async fn transfer(&self, url: &str, headers: &[AssetHeader], part: &Path, policy: &HostPolicy, pacer: &Pacer) -> Result<u64> {
+        if let Some(written) = self.transfer_part(url, headers, part, policy, pacer).await? {
+            return Ok(written);
+        }
+        // The stale part was removed, so this attempt starts from scratch
+        self.transfer_part(url, headers, part, policy, pacer).await?
+            .ok_or_else(|| anyhow!("unexpected range response from {}", url))
+    }
+
+    /// One pass of `transfer`. Returns None, after removing the part, when the server reports the part
+    /// does not match the resource.
+    async fn transfer_part(
+        &self,
+        url: &str,
+        headers: &[AssetHeader],
+        part: &Path,
+        policy: &HostPolicy,
+        pacer: &Pacer,
+    ) -> Result<Option<u64>> {
+        let offset = tokio::fs::metadata(part).await.map(|m| m.len()).unwrap_or(0);
+        let mut url = Url::parse(url)?;
+        let mut redirects = 0;
+        let resp = loop {
+            if !url_permitted(policy, url.as_str()) {
+                bail!("{} is not within the plugin's allowed hosts", url);
+            }
+            pacer.wait(self.options.request_interval).await;
+            let mut req = apply_headers(self.client.get(url.clone()), headers);
+            if offset > 0 {
+                req = req.header(RANGE, format!("bytes={}-", offset));
+            }
+            let resp = req.send().await?;
+            if !resp.status().is_redirection() {
+                break resp;
+            }
+            redirects += 1;
+            if redirects > MAX_REDIRECTS {
+                bail!("too many redirects");
+            }
+            let location = resp.headers().get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow!("redirect without location from {}", url))?;
+            url = url.join(location)?;
+        };
+
+        let status = resp.status();
+        let content_range = resp.headers().get(CONTENT_RANGE).and_then(|v| v.to_str().ok());
+        let (mut file, mut written) = match part_response(status, content_range, offset) {
+            Some(PartResponse::Append) => (tokio::fs::OpenOptions::new().append(true).open(part).await?, offset),
+            Some(PartResponse::Restart) => (tokio::fs::File::create(part).await?, 0),
+            Some(PartResponse::Complete) => return Ok(Some(offset)),
+            Some(PartResponse::Discard) => {
+                debug!(url=%url, offset, ?content_range, "discarding partial download that does not match the resource");
+                tokio::fs::remove_file(part).await
+                    .with_context(|| format!("failed to remove {}", part.display()))?;
+                return Ok(None);
+            }
+            None => return Err(HttpStatusError { status, url }.into()),
+        };
+
+        let mut body = resp.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            if let Some(rate) = self.options.max_bytes_per_sec.filter(|r| *r > 0) {
+                self.bandwidth.wait(Duration::from_secs_f64(chunk.len() as f64 / rate as f64)).await;
+            }
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok(Some(written))
+    }
+}
+
+/// An error status from an asset server
+#[derive(Debug)]
+struct HttpStatusError {
+    status: StatusCode,
+    url: Url,
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP {} from {}", self.status, self.url)
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// Whether a failed transfer is worth retrying. Client errors other than timeouts and rate limiting
+/// will not change on their own.
+fn is_transient(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<HttpStatusError>() {
+        Some(HttpStatusError { status, .. }) => {
+            !status.is_client_error() || *status == StatusCode::REQUEST_TIMEOUT || *status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => true,
+    }
+}
+
+/// What to do with a part holding `offset` bytes given the server's answer to the (ranged) request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PartResponse {
+    /// The body continues the part
+    Append,
+    /// The body is the whole resource and replaces the part
+    Restart,
+    /// The part already holds the whole resource
+    Complete,
+    /// The part does not match the resource and has to be thrown away
+    Discard,
+}
+
+/// Decide how to treat a response; None when it cannot be used. A 206 only continues the part when it starts
+/// exactly at `offset`, and a 416 only completes it when the server's total length confirms it, so a
+/// stale or oversized part (e.g. left over from a URL that has since changed) is discarded.
+fn part_response(status: StatusCode, content_range: Option<&str>, offset: u64) -> Option<PartResponse> {
+    let range = content_range.and_then(parse_content_range);
+    if offset > 0 && status == StatusCode::PARTIAL_CONTENT {
+        Some(match range {
+            Some((Some(start), _)) if start == offset => PartResponse::Append,
+            _ => PartResponse::Discard,
+        })
+    } else if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+        Some(match range {
+            Some((None, Some(total))) if total == offset => PartResponse::Complete,
+            _ => PartResponse::Discard,
+        })
+    } else if status == StatusCode::PARTIAL_CONTENT {
+        // Ranges are only requested to resume; a 206 for a full request must cover the resource from its start
+        match range {
+            Some((Some(0), _)) => Some(PartResponse::Restart),
+            _ => None,
+        }
+    } else if status.is_success() {
+        Some(PartResponse::Restart)
+    } else {
+        None
+    }
+}
+
+/// Parse a `Content-Range` value into the first byte served (None for `*`) and the total length (None when unknown)
+fn parse_content_range(value: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse::<u64>().ok()?),
+    };
+    let start = match range.trim() {
+        "*" => None,
+        range => {
+            let (start, end) = range.split_once('-')?;
+            let (start, end) = (start.parse::<u64>().ok()?, end.parse::<u64>().ok()?);
+            if end < start {
+                return None;
+            }
+            Some(start)
+        }
+    };
+    Some((start, total))
+}
+
+/// Host-side fetches only ever use http(s), even for plugins without a host allow-list
+pub(crate) fn url_permitted(policy: &HostPolicy, url: &str) -> bool {
+    matches!(Url::parse(url).map(|u| u.scheme().to_string()).as_deref(), Ok("http" | "https"))
+        && policy.allows_url(url)
+}
+
+/// Store the plugin's transform of a fetched part at `path`. The part is kept until the result is written,
+/// so an interrupted job transforms the original bytes again rather than resuming into transformed ones.
+async fn transform_file(transform: &AssetTransform, file: &DownloadFile, part: &Path, path: &Path) -> Result<u64> {
+    let len = tokio::fs::metadata(part).await
+        .with_context(|| format!("failed to read {}", part.display()))?
+        .len();
+    if len > MAX_TRANSFORM_BYTES {
+        bail!("{} is too large to transform ({} bytes)", file.url, len);
+    }
+    let asset = file_asset(file);
+    let bytes = tokio::fs::read(part).await
+        .with_context(|| format!("failed to read {}", part.display()))?;
+    let bytes = transform.apply(&asset, bytes).await
+        .with_context(|| format!("failed to transform {}", file.url))?;
+    tokio::fs::write(path, &bytes).await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    tokio::fs::remove_file(part).await
+        .with_context(|| format!("failed to remove {}", part.display()))?;
+    Ok(bytes.len() as u64)
+}
+
+/// The asset a download file was created from, as far as the job records it
+fn file_asset(file: &DownloadFile) -> Asset {
+    Asset {
+        url: file.url.clone(),
+        mime: file.mime.clone(),
+        width: None,
+        height: None,
+        kind: file.kind.clone(),
+        headers: file.headers.clone(),
+        expires_at: None,
+    }
+}
+
+async fn remove_dir(dir: &Path) {
+    if let Err(e) = tokio::fs::remove_dir_all(dir).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!(dir=%dir.display(), error=%e, "failed to remove download directory");
+        }
+    }
+}
+
+/// Make an id safe to use as a single path component. Altered ids get a hash suffix so they stay unique.
+fn path_component(id: &str) -> String {
+    let cleaned: String = id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+    if cleaned == id && !id.is_empty() && id.len() <= MAX_COMPONENT_LEN {
+        return id.to_string();
+    }
+    let digest = Sha256::digest(id.as_bytes());
+    let hash: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+    let prefix: String = cleaned.chars().take(MAX_COMPONENT_LEN).collect();
+    format!("{}-{}", prefix, hash)
+}
+
+/// File extension for an asset, from its MIME type or else its URL path
+fn file_extension(asset: &Asset) -> String {
+    let from_mime = asset.mime.as_deref().and_then(|mime| {
+        let ext = match mime.split(';').next()?.trim() {
+            "image/jpeg" => "jpg",
+            "image/png" => "png",
+            "image/webp" => "webp",
+            "image/gif" => "gif",
+            "image/avif" => "avif",
+            "audio/mpeg" => "mp3",
+            "audio/aac" => "aac",
+            "audio/ogg" => "ogg",
+            "video/mp4" => "mp4",
+            "video/webm" => "webm",
+            "text/vtt" => "vtt",
+            "application/x-subrip" => "srt",
+            "application/pdf" => "pdf",
+            "application/epub+zip" => "epub",
+            _ => return None,
+        };
+        Some(ext.to_string())
+    });
+    from_mime
+        .or_else(|| {
+            let url = Url::parse(&asset.url).ok()?;
+            let name = url.path_segments()?.next_back()?.to_string();
+            let (_, ext) = name.rsplit_once('.')?;
+            (!ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+                .then(|| ext.to_ascii_lowercase())
+        })
+        .unwrap_or_else(|| "bin".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_range_is_parsed() {
+        assert_eq!(parse_content_range("bytes 100-199/200"), Some((Some(100), Some(200))));
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((Some(0), None)));
+        assert_eq!(parse_content_range("bytes */5000"), Some((None, Some(5000))));
+        assert_eq!(parse_content_range("bytes 9-0/10"), None);
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+        assert_eq!(parse_content_range("bytes x-9/10"), None);
+    }
+
+    #[test]
+    fn partial_content_must_start_at_offset() {
+        let partial = StatusCode::PARTIAL_CONTENT;
+        assert_eq!(part_response(partial, Some("bytes 100-199/200"), 100), Some(PartResponse::Append));
+        assert_eq!(part_response(partial, Some("bytes 0-199/200"), 100), Some(PartResponse::Discard));
+        assert_eq!(part_response(partial, None, 100), Some(PartResponse::Discard));
+        assert_eq!(part_response(partial, Some("bytes 0-199/200"), 0), Some(PartResponse::Restart));
+        assert_eq!(part_response(partial, Some("bytes 50-199/200"), 0), None);
+    }
+
+    #[test]
+    fn unsatisfiable_range_completes_only_on_matching_length() {
+        let unsatisfiable = StatusCode::RANGE_NOT_SATISFIABLE;
+        assert_eq!(part_response(unsatisfiable, Some("bytes */200"), 200), Some(PartResponse::Complete));
+        assert_eq!(part_response(unsatisfiable, Some("bytes */150"), 200), Some(PartResponse::Discard));
+        assert_eq!(part_response(unsatisfiable, None, 200), Some(PartResponse::Discard));
+        assert_eq!(part_response(unsatisfiable, Some("bytes */200"), 0), None);
+    }
+
+    #[test]
+    fn full_responses_restart_and_errors_are_rejected() {
+        assert_eq!(part_response(StatusCode::OK, None, 0), Some(PartResponse::Restart));
+        assert_eq!(part_response(StatusCode::OK, None, 100), Some(PartResponse::Restart));
+        assert_eq!(part_response(StatusCode::NOT_FOUND, None, 0), None);
+        assert_eq!(part_response(StatusCode::INTERNAL_SERVER_ERROR, None, 100), None);
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        let url = Url::parse("https://example.com/a.png").unwrap();
+        let status = |status| anyhow::Error::from(HttpStatusError { status, url: url.clone() });
+        assert!(!is_transient(&status(StatusCode::NOT_FOUND)));
+        assert!(!is_transient(&status(StatusCode::FORBIDDEN)));
+        assert!(is_transient(&status(StatusCode::TOO_MANY_REQUESTS)));
+        assert!(is_transient(&status(StatusCode::REQUEST_TIMEOUT)));
+        assert!(is_transient(&status(StatusCode::BAD_GATEWAY)));
+        assert!(is_transient(&anyhow!("connection reset")));
+    }
+}
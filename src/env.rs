@@ -5,14 +5,20 @@ use tracing_subscriber::{fmt, EnvFilter};
 pub struct Config {
     pub db_path: Option<PathBuf>,
     pub plugins_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub downloads_dir: Option<PathBuf>,
+    pub local_library_dir: Option<PathBuf>,
     pub run_migrations: bool,
 }
 
 impl Config {
     /// Create a new Config, setting default environment variables if not already set.
     pub fn new() -> Self {
-        let mut db_path: Option<PathBuf> = None;
+        let db_path: Option<PathBuf>;
         let mut plugins_dir: Option<PathBuf> = None;
+        let cache_dir: Option<PathBuf>;
+        let downloads_dir: Option<PathBuf>;
+        let local_library_dir: Option<PathBuf>;
         let mut run_migrations = true; // default to true
 
         let _ = fmt()
@@ -26,18 +32,21 @@ impl Config {
             );
         } // default to info level logging, with more verbose logging for HTTP and WASI components
 
-        if std::env::var("DATABASE_URL").is_err() {
-            if let Some(proj_dirs) = directories::ProjectDirs::from("com", "fiveeus", "aWASMlib") {
-                let app_support_dir = proj_dirs.data_dir();
-                std::fs::create_dir_all(app_support_dir).ok();
-                db_path = Some(app_support_dir.join("awasmlib.db"));
-                std::env::set_var("DATABASE_URL", format!("sqlite://{}", db_path.as_ref().unwrap().to_string_lossy()));
-            } else {
-                // Fallback to a sensible default if ProjectDirs fails
-                let fallback_path = PathBuf::from("awasmlib.db");
-                db_path = Some(fallback_path.clone());
-                std::env::set_var("DATABASE_URL", format!("sqlite://{}", fallback_path.to_string_lossy()));
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            db_path = db_path_from_url(&url);
+            if db_path.is_none() {
+                tracing::warn!(url=%url, "DATABASE_URL is not a sqlite file URL or path; database disabled");
             }
+        } else if let Some(proj_dirs) = directories::ProjectDirs::from("com", "fiveeus", "aWASMlib") {
+            let app_support_dir = proj_dirs.data_dir();
+            std::fs::create_dir_all(app_support_dir).ok();
+            db_path = Some(app_support_dir.join("awasmlib.db"));
+            std::env::set_var("DATABASE_URL", format!("sqlite://{}", db_path.as_ref().unwrap().to_string_lossy()));
+        } else {
+            // Fallback to a sensible default if ProjectDirs fails
+            let fallback_path = PathBuf::from("awasmlib.db");
+            db_path = Some(fallback_path.clone());
+            std::env::set_var("DATABASE_URL", format!("sqlite://{}", fallback_path.to_string_lossy()));
         }
 
         if std::env::var("PLUGINS_DIR").is_err() {
@@ -54,6 +63,38 @@ impl Config {
             }
         }
 
+        if let Ok(dir) = std::env::var("CACHE_DIR") {
+            cache_dir = Some(PathBuf::from(dir));
+        } else if let Some(proj_dirs) = directories::ProjectDirs::from("com", "fiveeus", "aWASMlib") {
+            cache_dir = Some(proj_dirs.data_dir().join("cache"));
+            std::env::set_var("CACHE_DIR", cache_dir.as_ref().unwrap().to_string_lossy().to_string());
+        } else {
+            // Fallback to a sensible default if ProjectDirs fails
+            cache_dir = Some(PathBuf::from("cache"));
+            std::env::set_var("CACHE_DIR", "cache");
+        } // compiled plugin cache, created lazily by the PluginManager
+
+        if let Ok(dir) = std::env::var("DOWNLOADS_DIR") {
+            downloads_dir = Some(PathBuf::from(dir));
+        } else if let Some(proj_dirs) = directories::ProjectDirs::from("com", "fiveeus", "aWASMlib") {
+            downloads_dir = Some(proj_dirs.data_dir().join("downloads"));
+            std::env::set_var("DOWNLOADS_DIR", downloads_dir.as_ref().unwrap().to_string_lossy().to_string());
+        } else {
+            // Fallback to a sensible default if ProjectDirs fails
+            downloads_dir = Some(PathBuf::from("downloads"));
+            std::env::set_var("DOWNLOADS_DIR", "downloads");
+        } // offline downloads, created by the download manager
+
+        if let Ok(dir) = std::env::var("LOCAL_LIBRARY_DIR") {
+            local_library_dir = Some(PathBuf::from(dir));
+        } else if let Some(proj_dirs) = directories::ProjectDirs::from("com", "fiveeus", "aWASMlib") {
+            local_library_dir = Some(proj_dirs.data_dir().join("library"));
+            std::fs::create_dir_all(local_library_dir.as_ref().unwrap()).ok();
+            std::env::set_var("LOCAL_LIBRARY_DIR", local_library_dir.as_ref().unwrap().to_string_lossy().to_string());
+        } else {
+            local_library_dir = None;
+        } // local files served by the built-in `local` provider
+
         if std::env::var("RUN_MIGRATIONS").is_err() {
             std::env::set_var("RUN_MIGRATIONS", "true");
             run_migrations = true;
@@ -61,6 +102,38 @@ impl Config {
             run_migrations = val == "true";
         } // determine whether to run migrations based on environment variable
 
-        Self { db_path, plugins_dir, run_migrations }
+        Self { db_path, plugins_dir, cache_dir, downloads_dir, local_library_dir, run_migrations }
+    }
+}
+
+/// File path named by a `DATABASE_URL`: `sqlite://path`, `sqlite:path` or a bare path.
+/// Query parameters are ignored; other schemes and in-memory databases yield None.
+fn db_path_from_url(url: &str) -> Option<PathBuf> {
+    let url = url.trim();
+    let path = match url.strip_prefix("sqlite://").or_else(|| url.strip_prefix("sqlite:")) {
+        Some(rest) => rest,
+        None if url.contains("://") => return None,
+        None => url,
+    };
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    if path.is_empty() || path == ":memory:" {
+        return None;
     }
-}
\ No newline at end of file
+    Some(PathBuf::from(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_url_forms() {
+        assert_eq!(db_path_from_url("sqlite:///var/lib/a.db"), Some(PathBuf::from("/var/lib/a.db")));
+        assert_eq!(db_path_from_url("sqlite://a.db?mode=rwc"), Some(PathBuf::from("a.db")));
+        assert_eq!(db_path_from_url("sqlite:a.db"), Some(PathBuf::from("a.db")));
+        assert_eq!(db_path_from_url("/tmp/a.db"), Some(PathBuf::from("/tmp/a.db")));
+        assert_eq!(db_path_from_url("postgres://localhost/a"), None);
+        assert_eq!(db_path_from_url("sqlite::memory:"), None);
+        assert_eq!(db_path_from_url(""), None);
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::database::now_secs;
+use crate::plugins::{Media, Unit};
+
+/// Nominal page size of exported fixed-layout EPUBs; pages are scaled to fit it
+const EPUB_VIEWPORT: (u32, u32) = (1200, 1800);
+
+/// Archive formats downloaded units can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comic book zip with a ComicInfo.xml
+    Cbz,
+    /// Fixed-layout EPUB 3
+    Epub,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Cbz => "cbz",
+            ExportFormat::Epub => "epub",
+        }
+    }
+}
+
+/// Pages of one unit, in reading order
+#[derive(Debug, Clone)]
+pub struct ExportChapter {
+    pub unit: Unit,
+    pub pages: Vec<PathBuf>,
+}
+
+/// One or more units bound into a single book
+#[derive(Debug, Clone)]
+pub struct ExportBook {
+    pub title: String,
+    pub media: Option<Media>,
+    pub chapters: Vec<ExportChapter>,
+}
+
+impl ExportBook {
+    fn page_count(&self) -> usize {
+        self.chapters.iter().map(|c| c.pages.len()).sum()
+    }
+
+    fn language(&self) -> &str {
+        self.chapters.iter().find_map(|c| c.unit.lang.as_deref()).unwrap_or("und")
+    }
+
+    /// Pages across all chapters with their archive names
+    fn named_pages(&self) -> Vec<(String, &Path)> {
+        let mut names = Vec::with_capacity(self.page_count());
+        for (ci, chapter) in self.chapters.iter().enumerate() {
+            for (pi, page) in chapter.pages.iter().enumerate() {
+                let ext = page.extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_ascii_lowercase();
+                let name = if self.chapters.len() > 1 {
+                    format!("{:03}-{:04}.{}", ci + 1, pi + 1, ext)
+                } else {
+                    format!("{:04}.{}", pi + 1, ext)
+                };
+                names.push((name, page.as_path()));
+            }
+        }
+        names
+    }
+}
+
+/// Write the book to `dest` in the given format
+pub fn export_book(book: &ExportBook, format: ExportFormat, dest: &Path) -> Result<()> {
+    if book.page_count() == 0 {
+        bail!("nothing to export for {}", book.title);
+    }
+    let tmp = crate::tmp::sibling(dest);
+    let res = match format {
+        ExportFormat::Cbz => write_cbz(book, &tmp),
+        ExportFormat::Epub => write_epub(book, &tmp),
+    };
+    if let Err(e) = res {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e.context(format!("failed to export {}", dest.display())));
+    }
+    std::fs::rename(&tmp, dest).with_context(|| format!("failed to write {}", dest.display()))
+}
+
+fn write_cbz(book: &ExportBook, dest: &Path) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, path) in book.named_pages() {
+        zip.start_file(name, stored)?;
+        zip.write_all(&std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?)?;
+    }
+    zip.start_file("ComicInfo.xml", deflated)?;
+    zip.write_all(comic_info(book).as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// ComicInfo.xml (Anansi schema v2.0) describing the book
+fn comic_info(book: &ExportBook) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+    );
+    let mut field = |name: &str, value: Option<&str>| {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            xml.push_str(&format!("  <{0}>{1}</{0}>\n", name, escape(value)));
+        }
+    };
+    let first = &book.chapters[0].unit;
+    let series = book.media.as_ref().map(|m| m.title.as_str());
+    if book.chapters.len() == 1 {
+        let number = first.number_text.clone().or_else(|| first.number.map(|n| n.to_string()));
+        field("Title", Some(&first.title));
+        field("Series", series);
+        field("Number", number.as_deref());
+    } else {
+        field("Title", Some(&book.title));
+        field("Series", series);
+    }
+    let volume = first.group.as_deref().and_then(leading_number);
+    if book.chapters.iter().all(|c| c.unit.group == first.group) {
+        field("Volume", volume.as_deref());
+    }
+    field("Summary", book.media.as_ref().and_then(|m| m.description.as_deref()));
+    if let Some((year, month, day)) = first.published_at.as_deref().and_then(parse_date) {
+        field("Year", Some(&year.to_string()));
+        field("Month", Some(&month.to_string()));
+        field("Day", Some(&day.to_string()));
+    }
+    field("ScanInformation", first.upload_group.as_deref());
+    field("Web", first.url.as_deref().or(book.media.as_ref().and_then(|m| m.url.as_deref())));
+    field("PageCount", Some(&book.page_count().to_string()));
+    field("LanguageISO", first.lang.as_deref());
+
+    xml.push_str("  <Pages>\n");
+    let mut image = 0;
+    for chapter in &book.chapters {
+        for i in 0..chapter.pages.len() {
+            let mut attrs = format!("Image=\"{}\"", image);
+            if image == 0 {
+                attrs.push_str(" Type=\"FrontCover\"");
+            }
+            if i == 0 && book.chapters.len() > 1 {
+                attrs.push_str(&format!(" Bookmark=\"{}\"", escape(&chapter.unit.title)));
+            }
+            xml.push_str(&format!("    <Page {} />\n", attrs));
+            image += 1;
+        }
+    }
+    xml.push_str("  </Pages>\n</ComicInfo>\n");
+    xml
+}
+
+fn write_epub(book: &ExportBook, dest: &Path) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // The mimetype entry must come first and be uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(
+        b"<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+          <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+          \x20 <rootfiles>\n\
+          \x20   <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+          \x20 </rootfiles>\n\
+          </container>\n",
+    )?;
+
+    let (width, height) = EPUB_VIEWPORT;
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut toc = String::new();
+    let mut page_index = 0;
+    let pages = book.named_pages();
+    for chapter in &book.chapters {
+        for _ in &chapter.pages {
+            let (image_name, path) = &pages[page_index];
+            page_index += 1;
+            let id = format!("p{:05}", page_index);
+            let media_type = image_media_type(image_name);
+            zip.start_file(format!("OEBPS/images/{}", image_name), stored)?;
+            zip.write_all(&std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?)?;
+            zip.start_file(format!("OEBPS/{}.xhtml", id), deflated)?;
+            zip.write_all(
+                format!(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                     <!DOCTYPE html>\n\
+                     <html xmlns=\"http://www.w3.org/1999/xhtml\">\n\
+                     <head><title>{title}</title><meta name=\"viewport\" content=\"width={w}, height={h}\"/>\n\
+                     <style>html, body {{ margin: 0; padding: 0; width: {w}px; height: {h}px; }} \
+                     img {{ width: 100%; height: 100%; object-fit: contain; }}</style></head>\n\
+                     <body><img src=\"images/{image}\" alt=\"\"/></body>\n\
+                     </html>\n",
+                    title = escape(&chapter.unit.title),
+                    w = width,
+                    h = height,
+                    image = image_name,
+                )
+                .as_bytes(),
+            )?;
+            let cover = if page_index == 1 { " properties=\"cover-image\"" } else { "" };
+            manifest.push_str(&format!(
+                "    <item id=\"{id}\" href=\"{id}.xhtml\" media-type=\"application/xhtml+xml\"/>\n\
+                 \x20   <item id=\"{id}-img\" href=\"images/{image}\" media-type=\"{mt}\"{cover}/>\n",
+                id = id,
+                image = image_name,
+                mt = media_type,
+                cover = cover,
+            ));
+            spine.push_str(&format!("    <itemref idref=\"{}\"/>\n", id));
+        }
+        if !chapter.pages.is_empty() {
+            toc.push_str(&format!(
+                "      <li><a href=\"p{:05}.xhtml\">{}</a></li>\n",
+                page_index - chapter.pages.len() + 1,
+                escape(if chapter.unit.title.is_empty() { "Chapter" } else { &chapter.unit.title }),
+            ));
+        }
+    }
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <!DOCTYPE html>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+             <head><title>{title}</title></head>\n\
+             <body>\n  <nav epub:type=\"toc\">\n    <ol>\n{toc}    </ol>\n  </nav>\n</body>\n</html>\n",
+            title = escape(&book.title),
+            toc = toc,
+        )
+        .as_bytes(),
+    )?;
+
+    let description = book.media.as_ref().and_then(|m| m.description.as_deref())
+        .map(|d| format!("    <dc:description>{}</dc:description>\n", escape(d)))
+        .unwrap_or_default();
+    let identifier = match &book.media {
+        Some(media) => format!("urn:awasmlib:{}:{}", media.id, book.title),
+        None => format!("urn:awasmlib:{}", book.title),
+    };
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{lang}\">\n\
+             \x20 <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             \x20   <dc:identifier id=\"book-id\">{identifier}</dc:identifier>\n\
+             \x20   <dc:title>{title}</dc:title>\n\
+             \x20   <dc:language>{lang}</dc:language>\n\
+             {description}\
+             \x20   <meta property=\"dcterms:modified\">{modified}</meta>\n\
+             \x20   <meta property=\"rendition:layout\">pre-paginated</meta>\n\
+             \x20   <meta property=\"rendition:spread\">none</meta>\n\
+             \x20 </metadata>\n\
+             \x20 <manifest>\n\
+             \x20   <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+             {manifest}\
+             \x20 </manifest>\n\
+             \x20 <spine>\n\
+             {spine}\
+             \x20 </spine>\n\
+             </package>\n",
+            lang = escape(book.language()),
+            identifier = escape(&identifier),
+            title = escape(&book.title),
+            description = description,
+            modified = utc_timestamp(now_secs()),
+            manifest = manifest,
+            spine = spine,
+        )
+        .as_bytes(),
+    )?;
+    zip.finish()?;
+    Ok(())
+}
+
+fn image_media_type(name: &str) -> &'static str {
+    match name.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("svg") => "image/svg+xml",
+        _ => "image/jpeg",
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// First run of digits in a label such as "Vol. 3"
+fn leading_number(label: &str) -> Option<String> {
+    let digits: String = label.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    (!digits.is_empty()).then_some(digits)
+}
+
+/// Date part of an RFC 3339 / ISO 8601 timestamp
+fn parse_date(s: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = s.get(..10)?.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Some((year, month, day))
+}
+
+/// Format unix seconds as `YYYY-MM-DDThh:mm:ssZ`
+fn utc_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3_600, rem % 3_600 / 60, rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::UnitKind;
+
+    fn unit(title: &str) -> Unit {
+        Unit {
+            id: title.to_string(),
+            title: title.to_string(),
+            number_text: Some("12.5".to_string()),
+            number: Some(12.5),
+            lang: Some("en".to_string()),
+            group: Some("Vol. 3".to_string()),
+            url: Some("https://example.com/c/1".to_string()),
+            published_at: Some("2024-02-09T10:00:00Z".to_string()),
+            kind: UnitKind::Chapter,
+            upload_group: None,
+        }
+    }
+
+    fn chapter(title: &str, pages: &[&str]) -> ExportChapter {
+        ExportChapter { unit: unit(title), pages: pages.iter().map(PathBuf::from).collect() }
+    }
+
+    fn book(chapters: Vec<ExportChapter>) -> ExportBook {
+        ExportBook { title: "Book".to_string(), media: None, chapters }
+    }
+
+    fn names(book: &ExportBook) -> Vec<String> {
+        book.named_pages().into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn escape_replaces_markup_characters() {
+        assert_eq!(escape(r#"a & <b> "c" 'd'"#), "a &amp; &lt;b&gt; &quot;c&quot; &apos;d&apos;");
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn single_chapter_pages_are_numbered_alone() {
+        let book = book(vec![chapter("One", &["/d/1.PNG", "/d/2.webp", "/d/3"])]);
+        assert_eq!(names(&book), vec!["0001.png", "0002.webp", "0003.jpg"]);
+    }
+
+    #[test]
+    fn multi_chapter_pages_are_prefixed_with_the_chapter() {
+        let book = book(vec![chapter("One", &["/d/1.jpg", "/d/2.jpg"]), chapter("Two", &["/e/1.png"])]);
+        assert_eq!(names(&book), vec!["001-0001.jpg", "001-0002.jpg", "002-0001.png"]);
+    }
+
+    #[test]
+    fn comic_info_without_media() {
+        let xml = comic_info(&book(vec![chapter("Fish & <Chips>", &["/d/1.jpg", "/d/2.jpg"])]));
+        assert!(xml.contains("<Title>Fish &amp; &lt;Chips&gt;</Title>"));
+        assert!(xml.contains("<Number>12.5</Number>"));
+        assert!(xml.contains("<Volume>3</Volume>"));
+        assert!(xml.contains("<Year>2024</Year>") && xml.contains("<Month>2</Month>") && xml.contains("<Day>9</Day>"));
+        assert!(xml.contains("<Web>https://example.com/c/1</Web>"));
+        assert!(xml.contains("<PageCount>2</PageCount>"));
+        assert!(xml.contains("<LanguageISO>en</LanguageISO>"));
+        assert!(!xml.contains("<Series>") && !xml.contains("<Summary>") && !xml.contains("<ScanInformation>"));
+        assert!(xml.contains("<Page Image=\"0\" Type=\"FrontCover\" />"));
+        assert!(xml.contains("<Page Image=\"1\" />"));
+        assert!(!xml.contains("Bookmark"));
+    }
+
+    #[test]
+    fn comic_info_bookmarks_chapters_of_a_bound_book() {
+        let xml = comic_info(&book(vec![chapter("One", &["/d/1.jpg"]), chapter("Two \"b\"", &["/e/1.jpg"])]));
+        assert!(xml.contains("<Title>Book</Title>"));
+        assert!(!xml.contains("<Number>"));
+        assert!(xml.contains("<Page Image=\"1\" Bookmark=\"Two &quot;b&quot;\" />"));
+    }
+}
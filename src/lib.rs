@@ -1,7 +1,11 @@
 pub mod aggregator;
 pub mod plugins;
 pub mod database;
+pub mod downloads;
+pub mod export;
+pub mod proxy;
 pub mod env;
+mod tmp;
 /// Prelude re-exports commonly used types for easy import
 pub mod prelude {
     pub use crate::aggregator::Aggregator;
@@ -11,6 +15,8 @@ pub mod prelude {
 
 use crate::env::Config;
 use aggregator::Aggregator;
+use downloads::DownloadOptions;
+use proxy::ProxyOptions;
 
 use anyhow::{Result, bail};
 
@@ -27,16 +33,54 @@ impl Handle {
     /// run_migrations defaults to true.
     pub async fn new() -> Result<Self> {
         let config = Config::new();
-        let agg = Aggregator::new().await?;
+        let mut agg = Aggregator::new().await?;
+        if let Some(cache_dir) = &config.cache_dir {
+            if let Err(e) = agg.pm.enable_compile_cache(&cache_dir.join("plugins")) {
+                tracing::warn!(error=%e, "plugin compile cache unavailable");
+            }
+        }
+        if let Some(downloads_dir) = &config.downloads_dir {
+            if let Err(e) = agg.enable_downloads(downloads_dir, DownloadOptions::default()) {
+                tracing::warn!(error=%e, "downloads unavailable");
+            }
+        }
+        if let Some(library_dir) = &config.local_library_dir {
+            if let Err(e) = agg.pm.register_local_library(library_dir) {
+                tracing::warn!(error=%e, "local library unavailable");
+            }
+        }
         Ok(Self { agg, config })
     }
 
-    /// Connect to the connection string specified in the configuration.
+    /// Connect to the database specified in the configuration, applying migrations if enabled.
+    /// Downloads interrupted by a previous run are marked paused so they can be resumed.
     pub async fn connect(&self) -> Result<()> {
         match &self.config.db_path {
-            Some(database_url) => self.agg.db.connect(database_url).await,
+            Some(database_path) => self.agg.db.connect(database_path, self.config.run_migrations).await?,
             None => bail!("No database URL configured"),
         }
+        if self.agg.downloads().is_ok() {
+            if let Err(e) = self.agg.db.pause_interrupted_downloads().await {
+                tracing::warn!(error=%e, "failed to recover interrupted downloads");
+            }
+        }
+        Ok(())
+    }
+
+    /// Serve asset URLs returned by `fetch_assets` through a loopback proxy, caching images under the
+    /// configured cache directory
+    pub async fn enable_asset_proxy(&mut self) -> Result<()> {
+        let options = ProxyOptions {
+            cache_dir: self.config.cache_dir.as_ref().map(|dir| dir.join("images")),
+            ..ProxyOptions::default()
+        };
+        self.agg.enable_asset_proxy(options).await
+    }
+
+    /// Compile all registered plugins into the compile cache so the first use of each is fast.
+    /// Intended for installers and first-run setup, after `load_plugins`.
+    pub async fn precompile_plugins(&self) -> Result<()> {
+        self.agg.pm.precompile_all().await
     }
 
     /// Load plugins from the configured plugins directory. Specifically, it registers each plugin artifact found
@@ -1,22 +1,52 @@
-use std::collections::HashMap;
-use std::{path::PathBuf, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::{path::{Path, PathBuf}, time::Duration};
 use std::sync::{atomic::{AtomicU64, AtomicBool, Ordering}, Arc};
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::task;
-use tracing::{error, info, warn};
+use futures::stream::{self, Stream};
+use tracing::{debug, error, info, warn};
 use wasmtime::{Config, Engine};
 
+use cache::CompileCache;
+use native::NativeProvider;
 use plugin::Plugin;
 
-wasmtime::component::bindgen!({
-    world: "library",
-    path: "wit/",
-});
+/// Types of the original `library` world. Its `asset` is superseded by the `library-v2` record in `asset`.
+mod bindings {
+    wasmtime::component::bindgen!({
+        world: "library",
+        path: "wit/",
+        additional_derives: [PartialEq, serde::Serialize, serde::Deserialize],
+    });
+}
+
+pub use bindings::*;
 
 mod plugin;
+mod asset;
 mod host;
+mod cache;
 mod config;
+mod details;
+mod error;
+mod feeds;
+mod hosts;
+mod local;
+mod native;
+mod paging;
+mod sandbox;
+mod search;
+
+pub use asset::{Asset, AssetHeader};
+pub(crate) use asset::apply_headers;
+pub use details::{AltTitle, Credit, CreditRole, ExternalId, MediaDetails};
+pub use error::{PluginError, ProviderError};
+pub use feeds::{Feed, FeedKind};
+pub use paging::{Page, PageRequest};
+pub use search::{ContentRating, PublicationStatus, SearchCapabilities, SearchRequest, SortOrder, YearRange};
+pub(crate) use hosts::HostPolicy;
 
 // Commands routed to a dedicated worker thread per plugin
 enum PluginCmd {
@@ -29,16 +59,123 @@ enum PluginCmd {
         media_id: String,
         reply: oneshot::Sender<anyhow::Result<Vec<Unit>>>,
     },
+    FetchMediaDetails {
+        media_id: String,
+        reply: oneshot::Sender<anyhow::Result<MediaDetails>>,
+    },
+    FetchMediaPage {
+        kind: MediaType,
+        query: String,
+        page: PageRequest,
+        reply: oneshot::Sender<anyhow::Result<Page<Media>>>,
+    },
+    FetchUnitsPage {
+        media_id: String,
+        page: PageRequest,
+        reply: oneshot::Sender<anyhow::Result<Page<Unit>>>,
+    },
+    Search {
+        request: SearchRequest,
+        page: PageRequest,
+        reply: oneshot::Sender<anyhow::Result<Page<Media>>>,
+    },
+    GetSearchCapabilities {
+        reply: oneshot::Sender<anyhow::Result<Option<SearchCapabilities>>>,
+    },
+    ListFeeds {
+        reply: oneshot::Sender<anyhow::Result<Vec<Feed>>>,
+    },
+    FetchFeed {
+        feed_id: String,
+        cursor: Option<String>,
+        reply: oneshot::Sender<anyhow::Result<Page<Media>>>,
+    },
     FetchAssets {
         unit_id: String,
         reply: oneshot::Sender<anyhow::Result<Vec<Asset>>>,
     },
+    ReadAsset {
+        url: String,
+        reply: oneshot::Sender<anyhow::Result<Vec<u8>>>,
+    },
+    TransformsAssets {
+        reply: oneshot::Sender<anyhow::Result<bool>>,
+    },
+    TransformAsset {
+        asset: Asset,
+        bytes: Vec<u8>,
+        reply: oneshot::Sender<anyhow::Result<Vec<u8>>>,
+    },
     GetCapabilities {
+        refresh: bool,
         reply: oneshot::Sender<anyhow::Result<ProviderCapabilities>>,
     },
     GetAllowedHosts {
         reply: oneshot::Sender<anyhow::Result<Vec<String>>>,
+    },
+    GetHostPolicy {
+        reply: oneshot::Sender<anyhow::Result<HostPolicy>>,
+    },
+}
+
+impl PluginCmd {
+    /// Name of the command for logs
+    fn op_name(&self) -> &'static str {
+        match self {
+            PluginCmd::FetchMediaList { .. } => "fetchmedialist",
+            PluginCmd::FetchUnits { .. } => "fetchunits",
+            PluginCmd::FetchMediaDetails { .. } => "fetchmediadetails",
+            PluginCmd::FetchMediaPage { .. } => "fetchmedialist-page",
+            PluginCmd::FetchUnitsPage { .. } => "fetchunits-page",
+            PluginCmd::Search { .. } => "search",
+            PluginCmd::GetSearchCapabilities { .. } => "get-search-capabilities",
+            PluginCmd::ListFeeds { .. } => "list-feeds",
+            PluginCmd::FetchFeed { .. } => "fetch-feed",
+            PluginCmd::FetchAssets { .. } => "fetchassets",
+            PluginCmd::ReadAsset { .. } => "read-asset",
+            PluginCmd::TransformsAssets { .. } => "transforms-assets",
+            PluginCmd::TransformAsset { .. } => "transform-asset",
+            PluginCmd::GetCapabilities { .. } => "getcapabilities",
+            PluginCmd::GetAllowedHosts { .. } => "get-allowed-hosts",
+            PluginCmd::GetHostPolicy { .. } => "get-host-policy",
+        }
     }
+
+    /// Whether the caller already gave up (timed out or dropped a search stream) and closed its
+    /// reply channel, so the command can be skipped instead of calling into the plugin for nobody
+    fn is_abandoned(&self) -> bool {
+        match self {
+            PluginCmd::FetchMediaList { reply, .. } => reply.is_closed(),
+            PluginCmd::FetchUnits { reply, .. } => reply.is_closed(),
+            PluginCmd::FetchMediaDetails { reply, .. } => reply.is_closed(),
+            PluginCmd::FetchMediaPage { reply, .. } => reply.is_closed(),
+            PluginCmd::FetchUnitsPage { reply, .. } => reply.is_closed(),
+            PluginCmd::Search { reply, .. } => reply.is_closed(),
+            PluginCmd::GetSearchCapabilities { reply } => reply.is_closed(),
+            PluginCmd::ListFeeds { reply } => reply.is_closed(),
+            PluginCmd::FetchFeed { reply, .. } => reply.is_closed(),
+            PluginCmd::FetchAssets { reply, .. } => reply.is_closed(),
+            PluginCmd::ReadAsset { reply, .. } => reply.is_closed(),
+            PluginCmd::TransformsAssets { reply } => reply.is_closed(),
+            PluginCmd::TransformAsset { reply, .. } => reply.is_closed(),
+            PluginCmd::GetCapabilities { reply, .. } => reply.is_closed(),
+            PluginCmd::GetAllowedHosts { reply } => reply.is_closed(),
+            PluginCmd::GetHostPolicy { reply } => reply.is_closed(),
+        }
+    }
+}
+
+/// Which compiled form of a plugin was loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// Precompiled `.cwasm` deserialized directly
+    Precompiled,
+    /// `.wasm` component compiled at load time
+    Wasm,
+    /// `.wasm` component loaded from the on-disk compile cache
+    Cached,
+    /// Built-in provider implemented in Rust
+    Native,
 }
 
 /// Worker managing a single plugin instance
@@ -46,16 +183,28 @@ enum PluginCmd {
 struct PluginWorker {
     tx: mpsc::Sender<PluginCmd>,
     call_timeout: Duration,
+    artifact_kind: ArtifactKind,
+    artifact_path: PathBuf,
+}
+
+/// What a plugin slot runs
+enum SlotSource {
+    Wasm(PluginArtifacts),
+    Native(Arc<dyn NativeProvider>),
 }
 
 /// A loaded plugin instance
 struct PluginSlot {
     name: String,
-    artifacts: PluginArtifacts,
+    source: SlotSource,
     engine: Arc<Engine>,
     epoch_ticks: Arc<AtomicU64>,
     epoch_interval: Duration,
+    cache: Option<Arc<CompileCache>>,
     state: Mutex<Option<PluginWorker>>,
+    /// Whether the plugin advertises `transform-asset`, once it has been determined; cleared when
+    /// capabilities are refreshed
+    transforms_assets: std::sync::Mutex<Option<bool>>,
 }
 impl PluginSlot {
     /// Create a new PluginSlot struct (not yet initialized)
@@ -65,17 +214,41 @@ impl PluginSlot {
         engine: Arc<Engine>,
         epoch_ticks: Arc<AtomicU64>,
         epoch_interval: Duration,
+        cache: Option<Arc<CompileCache>>,
     ) -> Self {
-        Self {name, artifacts, engine, epoch_ticks, epoch_interval, state: Mutex::new(None)}
+        Self {
+            name,
+            source: SlotSource::Wasm(artifacts),
+            engine,
+            epoch_ticks,
+            epoch_interval,
+            cache,
+            state: Mutex::new(None),
+            transforms_assets: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Create a slot for a built-in provider
+    fn native(provider: Arc<dyn NativeProvider>, engine: Arc<Engine>, epoch_ticks: Arc<AtomicU64>, epoch_interval: Duration) -> Self {
+        Self {
+            name: provider.name().to_string(),
+            source: SlotSource::Native(provider),
+            engine,
+            epoch_ticks,
+            epoch_interval,
+            cache: None,
+            state: Mutex::new(None),
+            transforms_assets: std::sync::Mutex::new(None),
+        }
     }
 
     /// Initialize a plugin from the given artifact path
-    async fn init(&self, path_buf: &PathBuf) -> Result<PluginWorker> {
+    async fn init(&self, artifacts: &PluginArtifacts, path_buf: &PathBuf) -> Result<PluginWorker> {
         if !path_buf.exists() {
             return Err(anyhow!("missing plugin artifact: {}", path_buf.display()));
         }
 
-        let cfg_path = self.artifacts.config.clone();
+        let cfg_path = artifacts.config.clone();
         if !cfg_path.exists() {
             return Err(anyhow!("missing plugin config: {}", cfg_path.display()));
         }
@@ -84,6 +257,7 @@ impl PluginSlot {
         let engine = self.engine.clone();
         let epoch_ticks = self.epoch_ticks.clone();
         let interval = self.epoch_interval;
+        let cache = self.cache.clone();
         let path_to_load = path_buf.to_path_buf();
 
         let plugin = task::spawn_blocking(move || -> Result<Plugin> {
@@ -99,6 +273,7 @@ impl PluginSlot {
                 &path_to_load,
                 epoch_ticks,
                 interval,
+                cache,
                 rt_arc.clone(),
             );
             rt_arc.block_on(fut)
@@ -113,10 +288,15 @@ impl PluginSlot {
         })??;
 
         let call_timeout = plugin.call_timeout;
+        let artifact_kind = plugin.artifact_kind;
         let (tx, mut rx) = mpsc::channel::<PluginCmd>(64);
         std::thread::spawn(move || {
             let mut plugin = plugin;
             while let Some(cmd) = rx.blocking_recv() {
+                if cmd.is_abandoned() {
+                    debug!(plugin=%plugin.name, op=cmd.op_name(), "skipping abandoned command");
+                    continue;
+                }
                 match cmd {
                     PluginCmd::FetchMediaList { kind, query, reply } => {
                         let _ = reply.send(plugin.fetch_media_list(kind, &query));
@@ -124,21 +304,58 @@ impl PluginSlot {
                     PluginCmd::FetchUnits { media_id, reply } => {
                         let _ = reply.send(plugin.fetch_units(&media_id));
                     }
+                    PluginCmd::FetchMediaDetails { media_id, reply } => {
+                        let _ = reply.send(plugin.fetch_media_details(&media_id));
+                    }
+                    PluginCmd::FetchMediaPage { kind, query, page, reply } => {
+                        let _ = reply.send(plugin.fetch_media_page(kind, &query, &page));
+                    }
+                    PluginCmd::FetchUnitsPage { media_id, page, reply } => {
+                        let _ = reply.send(plugin.fetch_units_page(&media_id, &page));
+                    }
+                    PluginCmd::Search { request, page, reply } => {
+                        let _ = reply.send(plugin.search(&request, &page));
+                    }
+                    PluginCmd::GetSearchCapabilities { reply } => {
+                        let _ = reply.send(plugin.get_search_capabilities());
+                    }
+                    PluginCmd::ListFeeds { reply } => {
+                        let _ = reply.send(plugin.list_feeds());
+                    }
+                    PluginCmd::FetchFeed { feed_id, cursor, reply } => {
+                        let _ = reply.send(plugin.fetch_feed(&feed_id, cursor));
+                    }
                     PluginCmd::FetchAssets { unit_id, reply } => {
                         let _ = reply.send(plugin.fetch_assets(&unit_id));
                     }
-                    PluginCmd::GetCapabilities { reply } => {
+                    PluginCmd::TransformsAssets { reply } => {
+                        let _ = reply.send(plugin.transforms_assets());
+                    }
+                    PluginCmd::TransformAsset { asset, bytes, reply } => {
+                        let _ = reply.send(plugin.transform_asset(&asset, bytes));
+                    }
+                    PluginCmd::GetCapabilities { refresh: true, reply } => {
+                        let _ = reply.send(plugin.get_capabilities_refresh());
+                    }
+                    PluginCmd::GetCapabilities { refresh: false, reply } => {
                         let _ = reply.send(plugin.get_capabilities());
                     }
                     PluginCmd::GetAllowedHosts { reply } => {
                         let hosts = plugin.allowed_hosts.clone().unwrap_or_default();
                         let _ = reply.send(Ok(hosts));
                     }
+                    PluginCmd::GetHostPolicy { reply } => {
+                        let _ = reply.send(Ok(plugin.host_policy.clone()));
+                    }
+                    PluginCmd::ReadAsset { reply, .. } => {
+                        let reason = "only built-in providers serve asset contents".to_string();
+                        let _ = reply.send(Err(PluginError::InvalidRequest { plugin: plugin.name.clone(), reason }.into()));
+                    }
                 }
             }
         });
-        info!("Loaded plugin: {}", path_buf.display());
-        Ok(PluginWorker { tx, call_timeout })
+        info!(plugin=%self.name, path=%path_buf.display(), artifact=?artifact_kind, "loaded plugin");
+        Ok(PluginWorker { tx, call_timeout, artifact_kind, artifact_path: path_buf.clone() })
     }
 
     /// Get or create the PluginWorker for this slot
@@ -149,18 +366,27 @@ impl PluginSlot {
             return Ok(worker.clone());
         }
 
+        let artifacts = match &self.source {
+            SlotSource::Wasm(artifacts) => artifacts,
+            SlotSource::Native(provider) => {
+                let worker = native::spawn_worker(provider.clone());
+                *guard = Some(worker.clone());
+                return Ok(worker);
+            }
+        };
+
         // Otherwise, instantiate a new worker from the primary artifact, falling back if needed
-        let primary_path = &self.artifacts.primary.clone();
-        match self.init(primary_path).await {
+        let primary_path = &artifacts.primary.clone();
+        match self.init(artifacts, primary_path).await {
             Ok(worker) => {
                 *guard = Some(worker.clone());
                 return Ok(worker);
             }
             Err(mut err) => {
                 warn!(plugin=%self.name, path=%primary_path.display(), error=?err, "failed to load plugin artifact");
-                if let Some(fallback_path) = &self.artifacts.fallback {
+                if let Some(fallback_path) = &artifacts.fallback {
                     warn!(plugin=%self.name, path=%fallback_path.display(), error=?err, "attempting fallback artifact");
-                    match self.init(fallback_path).await {
+                    match self.init(artifacts, fallback_path).await {
                         Ok(worker) => {
                             *guard = Some(worker.clone());
                             return Ok(worker);
@@ -180,6 +406,16 @@ impl PluginSlot {
     fn name(&self) -> &str {
         &self.name
     }
+
+    /// The `.wasm` artifact of this plugin, if it has one
+    fn wasm_path(&self) -> Option<&PathBuf> {
+        let SlotSource::Wasm(artifacts) = &self.source else {
+            return None;
+        };
+        std::iter::once(&artifacts.primary)
+            .chain(artifacts.fallback.as_ref())
+            .find(|p| p.extension().and_then(|e| e.to_str()) == Some("wasm"))
+    }
 }
 
 
@@ -239,6 +475,7 @@ pub struct PluginManager {
     slots: Vec<Arc<PluginSlot>>,
     epoch_ticks: Arc<AtomicU64>,
     epoch_interval: Duration,
+    cache: Option<Arc<CompileCache>>,
     _epoch_stop: Arc<AtomicBool>,
     _epoch_thread: Option<std::thread::JoinHandle<()>>,
 }
@@ -276,15 +513,54 @@ impl PluginManager {
             slots: Vec::new(),
             epoch_ticks,
             epoch_interval,
+            cache: None,
             _epoch_stop: epoch_stop,
             _epoch_thread: Some(handle),
         })
     }
 
+    /// Cache compiled `.wasm` plugins under the given directory so later loads skip compilation.
+    /// Applies to plugins registered after this call.
+    pub fn enable_compile_cache(&mut self, dir: &Path) -> Result<()> {
+        self.cache = Some(Arc::new(CompileCache::new(&self.engine, dir)?));
+        info!(dir=%dir.display(), "enabled plugin compile cache");
+        Ok(())
+    }
+
+    /// Compile every registered `.wasm` plugin into the compile cache ahead of time.
+    /// Plugins that are already cached are skipped; failures are logged and reported together.
+    pub async fn precompile_all(&self) -> Result<()> {
+        let cache = self.cache.clone().ok_or_else(|| anyhow!("compile cache is not enabled"))?;
+        let mut failed = Vec::new();
+        for slot in &self.slots {
+            let Some(wasm) = slot.wasm_path().cloned() else {
+                continue;
+            };
+            let engine = self.engine.clone();
+            let cache = cache.clone();
+            let res = task::spawn_blocking(move || cache.precompile(&engine, &wasm))
+                .await
+                .map_err(|e| anyhow!("precompile task panicked: {}", e))
+                .and_then(|r| r);
+            match res {
+                Ok(compiled) => info!(plugin=%slot.name(), compiled, "plugin compile cache warm"),
+                Err(e) => {
+                    warn!(plugin=%slot.name(), error=%e, "failed to precompile plugin");
+                    failed.push(slot.name().to_string());
+                }
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("failed to precompile plugins: {}", failed.join(", ")))
+        }
+    }
+
     /// Load plugins from the specified directory, replacing any previously loaded plugins.
-    /// If the directory does not exist, no plugins will be loaded
+    /// Built-in providers stay registered. If the directory does not exist, no plugins will be loaded
     pub async fn load_plugins_from_directory(&mut self, dir: &PathBuf) -> Result<()> {
-        self.slots.clear();
+        self.slots.retain(|s| matches!(s.source, SlotSource::Native(_)));
         if !dir.exists() {
             warn!("Plugin directory does not exist: {}", dir.display());
             return Ok(());
@@ -319,12 +595,17 @@ impl PluginManager {
                 warn!(plugin=%name, config=%cfg_path.display(), "rejecting plugin: missing .toml config");
                 continue;
             }
+            if self.slots.iter().any(|s| s.name() == name) {
+                warn!(plugin=%name, "rejecting plugin: name is taken by a built-in provider");
+                continue;
+            }
             let slot = PluginSlot::new(
                 name.clone(),
                 artifacts,
                 self.engine.clone(),
                 self.epoch_ticks.clone(),
                 self.epoch_interval,
+                self.cache.clone(),
             );
             info!(plugin=%name, "registered plugin for lazy loading");
             self.slots.push(Arc::new(slot));
@@ -334,6 +615,21 @@ impl PluginManager {
         Ok(())
     }
 
+    /// Register the built-in local files provider over `dir`, under the plugin name `local`.
+    /// Replaces an earlier registration.
+    pub fn register_local_library(&mut self, dir: &Path) -> Result<()> {
+        if !dir.is_dir() {
+            return Err(anyhow!("local library directory does not exist: {}", dir.display()));
+        }
+        let provider: Arc<dyn NativeProvider> = Arc::new(local::LocalLibrary::new(dir));
+        self.slots.retain(|s| s.name() != provider.name());
+        let slot = PluginSlot::native(provider, self.engine.clone(), self.epoch_ticks.clone(), self.epoch_interval);
+        info!(plugin=%slot.name(), dir=%dir.display(), "registered local library");
+        self.slots.push(Arc::new(slot));
+        self.slots.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(())
+    }
+
     /// Get all plugin names
     pub fn list_plugins(&self) -> Vec<String> {
         self.slots
@@ -342,13 +638,30 @@ impl PluginManager {
             .collect()
     }
 
-    /// Get capabilities from all loaded plugins
-    pub async fn get_all_capabilities(&self, _refresh: bool) -> Result<HashMap<String, ProviderCapabilities>> {
+    /// Report which artifact a plugin was loaded from, or None if it has not been loaded yet
+    pub async fn loaded_artifact(&self, plugin_name: &str) -> Result<Option<(ArtifactKind, PathBuf)>> {
+        let slot = self.slot(plugin_name)?;
+        let guard = slot.state.lock().await;
+        Ok(guard.as_ref().map(|w| (w.artifact_kind, w.artifact_path.clone())))
+    }
+
+    /// Get capabilities from a specific plugin. Capabilities are cached by the plugin instance;
+    /// refresh asks the plugin again.
+    pub async fn get_capabilities(&self, plugin_name: &str, refresh: bool) -> Result<ProviderCapabilities> {
+        self.get_capabilities_task(plugin_name, refresh).await
+    }
+
+    /// Get capabilities from all loaded plugins. If refresh is true, plugins are asked again
+    /// instead of answering from their cached capabilities.
+    pub async fn get_all_capabilities(&self, refresh: bool) -> Result<HashMap<String, ProviderCapabilities>> {
         let mut results = HashMap::new();
         for slot in &self.slots {
+            if refresh {
+                *slot.transforms_assets.lock().unwrap() = None;
+            }
             let worker = slot.worker().await?;
             let (reply_tx, reply_rx) = oneshot::channel();
-            let cmd = PluginCmd::GetCapabilities { reply: reply_tx };
+            let cmd = PluginCmd::GetCapabilities { refresh, reply: reply_tx };
             if let Err(e) = worker.tx.send(cmd).await {
                 warn!(plugin=%slot.name(), "failed to send GetCapabilities command: {}", e);
                 continue;
@@ -371,10 +684,194 @@ impl PluginManager {
         Ok(results)
     }
 
+    /// Search a specific plugin for media of the given kind matching the query
+    pub async fn fetch_media_list(&self, plugin_name: &str, kind: MediaType, query: &str) -> Result<Vec<Media>> {
+        self.fetch_media_list_task(plugin_name, kind, query).await
+    }
+
+    /// List the units (chapters, episodes, ...) of a media item from a specific plugin
+    pub async fn fetch_units(&self, plugin_name: &str, media_id: &str) -> Result<Vec<Unit>> {
+        self.fetch_units_task(plugin_name, media_id).await
+    }
+
+    /// List the assets (pages, streams, ...) of a unit from a specific plugin
+    pub async fn fetch_assets(&self, plugin_name: &str, unit_id: &str) -> Result<Vec<Asset>> {
+        self.fetch_assets_task(plugin_name, unit_id).await
+    }
+
+    /// Read the contents of an asset a built-in provider serves without a fetchable URL, such as a page
+    /// inside a local CBZ. Such `file:` URLs cannot be downloaded, so frontends display them through this.
+    /// Fails with `PluginError::InvalidRequest` for wasm plugins.
+    pub async fn read_asset(&self, plugin_name: &str, url: &str) -> Result<Vec<u8>> {
+        let url = url.to_string();
+        self.call(plugin_name, "ReadAsset", |reply| PluginCmd::ReadAsset { url, reply }).await
+    }
+
+    /// Fetch extended metadata for a media item. Fails with `PluginError::InvalidRequest` for plugins
+    /// without a `fetchmediadetails` export.
+    pub async fn fetch_media_details(&self, plugin_name: &str, media_id: &str) -> Result<MediaDetails> {
+        self.fetch_media_details_task(plugin_name, media_id).await
+    }
+
+    /// Fetch one page of search results. Plugins without paged exports are paged by the host.
+    pub async fn fetch_media_page(&self, plugin_name: &str, kind: MediaType, query: &str, page: PageRequest) -> Result<Page<Media>> {
+        self.fetch_media_page_task(plugin_name, kind, query, page).await
+    }
+
+    /// Fetch one page of the units of a media item. Plugins without paged exports are paged by the host.
+    pub async fn fetch_units_page(&self, plugin_name: &str, media_id: &str, page: PageRequest) -> Result<Page<Unit>> {
+        self.fetch_units_page_task(plugin_name, media_id, page).await
+    }
+
+    /// Run a structured search. The request is checked against the plugin's advertised search capabilities
+    /// first and fails with `PluginError::InvalidRequest` without reaching the plugin if it uses anything
+    /// unsupported. Plain requests also work with plugins that only implement `fetchmedialist`.
+    pub async fn search(&self, plugin_name: &str, request: SearchRequest, page: PageRequest) -> Result<Page<Media>> {
+        self.call(plugin_name, "Search", |reply| PluginCmd::Search { request, page, reply }).await
+    }
+
+    /// Search filters a plugin supports, or None if it only understands a free-text query
+    pub async fn get_search_capabilities(&self, plugin_name: &str) -> Result<Option<SearchCapabilities>> {
+        self.call(plugin_name, "GetSearchCapabilities", |reply| PluginCmd::GetSearchCapabilities { reply }).await
+    }
+
+    /// Named browse lists (latest updates, popular, ...) offered by a plugin. Empty for plugins without feeds.
+    pub async fn list_feeds(&self, plugin_name: &str) -> Result<Vec<Feed>> {
+        self.call(plugin_name, "ListFeeds", |reply| PluginCmd::ListFeeds { reply }).await
+    }
+
+    /// Fetch one page of a feed. Pass the previous page's `next_cursor` to continue, or None for the top.
+    pub async fn fetch_feed(&self, plugin_name: &str, feed_id: &str, cursor: Option<String>) -> Result<Page<Media>> {
+        let feed_id = feed_id.to_string();
+        self.call(plugin_name, "FetchFeed", |reply| PluginCmd::FetchFeed { feed_id, cursor, reply }).await
+    }
+
+    /// Walk all pages of search results, `limit` items at a time. The stream ends after the last page
+    /// or the first error.
+    pub fn media_pages(
+        &self,
+        plugin_name: &str,
+        kind: MediaType,
+        query: &str,
+        limit: u32,
+    ) -> impl Stream<Item = Result<Page<Media>>> + Send + 'static {
+        let slot = self.slot(plugin_name).cloned().map_err(|e| e.to_string());
+        let query = query.to_string();
+        paginated(PageRequest::first(limit), move |page| {
+            let (kind, query) = (kind.clone(), query.clone());
+            call_slot(slot.clone().map_err(|e| anyhow!(e)), "FetchMediaPage", move |reply| {
+                PluginCmd::FetchMediaPage { kind, query, page, reply }
+            })
+        })
+    }
+
+    /// Walk all pages of a media item's units, `limit` items at a time. The stream ends after the last page
+    /// or the first error.
+    pub fn unit_pages(&self, plugin_name: &str, media_id: &str, limit: u32) -> impl Stream<Item = Result<Page<Unit>>> + Send + 'static {
+        let slot = self.slot(plugin_name).cloned().map_err(|e| e.to_string());
+        let media_id = media_id.to_string();
+        paginated(PageRequest::first(limit), move |page| {
+            let media_id = media_id.clone();
+            call_slot(slot.clone().map_err(|e| anyhow!(e)), "FetchUnitsPage", move |reply| {
+                PluginCmd::FetchUnitsPage { media_id, page, reply }
+            })
+        })
+    }
+
+    /// Owned-future variants of the fetch calls, for work that outlives the caller (e.g. cache revalidation)
+    pub(crate) fn fetch_media_list_task(
+        &self,
+        plugin_name: &str,
+        kind: MediaType,
+        query: &str,
+    ) -> impl Future<Output = Result<Vec<Media>>> + Send + 'static {
+        let query = query.to_string();
+        self.call(plugin_name, "FetchMediaList", |reply| PluginCmd::FetchMediaList { kind, query, reply })
+    }
+
+    pub(crate) fn fetch_units_task(&self, plugin_name: &str, media_id: &str) -> impl Future<Output = Result<Vec<Unit>>> + Send + 'static {
+        let media_id = media_id.to_string();
+        self.call(plugin_name, "FetchUnits", |reply| PluginCmd::FetchUnits { media_id, reply })
+    }
+
+    pub(crate) fn fetch_assets_task(&self, plugin_name: &str, unit_id: &str) -> impl Future<Output = Result<Vec<Asset>>> + Send + 'static {
+        let unit_id = unit_id.to_string();
+        self.call(plugin_name, "FetchAssets", |reply| PluginCmd::FetchAssets { unit_id, reply })
+    }
+
+    pub(crate) fn fetch_media_details_task(
+        &self,
+        plugin_name: &str,
+        media_id: &str,
+    ) -> impl Future<Output = Result<MediaDetails>> + Send + 'static {
+        let media_id = media_id.to_string();
+        self.call(plugin_name, "FetchMediaDetails", |reply| PluginCmd::FetchMediaDetails { media_id, reply })
+    }
+
+    pub(crate) fn fetch_media_page_task(
+        &self,
+        plugin_name: &str,
+        kind: MediaType,
+        query: &str,
+        page: PageRequest,
+    ) -> impl Future<Output = Result<Page<Media>>> + Send + 'static {
+        let query = query.to_string();
+        self.call(plugin_name, "FetchMediaPage", |reply| PluginCmd::FetchMediaPage { kind, query, page, reply })
+    }
+
+    pub(crate) fn fetch_units_page_task(
+        &self,
+        plugin_name: &str,
+        media_id: &str,
+        page: PageRequest,
+    ) -> impl Future<Output = Result<Page<Unit>>> + Send + 'static {
+        let media_id = media_id.to_string();
+        self.call(plugin_name, "FetchUnitsPage", |reply| PluginCmd::FetchUnitsPage { media_id, page, reply })
+    }
+
+    /// Read access to the `file:` assets of a built-in provider, or None for wasm plugins
+    pub(crate) fn asset_reader(&self, plugin_name: &str) -> Result<Option<AssetReader>> {
+        let slot = self.slot(plugin_name)?;
+        Ok(matches!(slot.source, SlotSource::Native(_)).then(|| AssetReader { slot: slot.clone() }))
+    }
+
+    /// The plugin's asset transform, or None if it does not advertise one. The answer is asked of the plugin
+    /// once per slot, until capabilities are refreshed.
+    pub(crate) async fn asset_transform(&self, plugin_name: &str) -> Result<Option<AssetTransform>> {
+        let slot = self.slot(plugin_name)?.clone();
+        let known = *slot.transforms_assets.lock().unwrap();
+        let needed = match known {
+            Some(needed) => needed,
+            None => {
+                let needed = call_slot(Ok(slot.clone()), "TransformsAssets", |reply| PluginCmd::TransformsAssets { reply }).await?;
+                *slot.transforms_assets.lock().unwrap() = Some(needed);
+                needed
+            }
+        };
+        Ok(needed.then_some(AssetTransform { slot }))
+    }
+
+    pub(crate) fn get_capabilities_task(
+        &self,
+        plugin_name: &str,
+        refresh: bool,
+    ) -> impl Future<Output = Result<ProviderCapabilities>> + Send + 'static {
+        if refresh {
+            if let Ok(slot) = self.slot(plugin_name) {
+                *slot.transforms_assets.lock().unwrap() = None;
+            }
+        }
+        self.call(plugin_name, "GetCapabilities", move |reply| PluginCmd::GetCapabilities { refresh, reply })
+    }
+
+    /// The host allow-list enforced for a plugin, for host-side requests made on its behalf
+    pub(crate) fn host_policy_task(&self, plugin_name: &str) -> impl Future<Output = Result<HostPolicy>> + Send + 'static {
+        self.call(plugin_name, "GetHostPolicy", |reply| PluginCmd::GetHostPolicy { reply })
+    }
+
     /// Get allowed hosts from a specific plugin
     pub async fn get_allowed_hosts(&self, plugin_name: &str) -> Result<Vec<String>> {
-        let slot = self.slots.iter().find(|s| s.name() == plugin_name)
-            .ok_or_else(|| anyhow!("plugin not found: {}", plugin_name))?;
+        let slot = self.slot(plugin_name)?;
         let worker = slot.worker().await?;
         let (reply_tx, reply_rx) = oneshot::channel();
         let cmd = PluginCmd::GetAllowedHosts { reply: reply_tx };
@@ -385,4 +882,148 @@ impl PluginManager {
             Err(_) => Err(anyhow!("GetAllowedHosts call timed out")),
         }
     }
-}
\ No newline at end of file
+
+    /// Find the slot registered under the given plugin name
+    fn slot(&self, plugin_name: &str) -> Result<&Arc<PluginSlot>> {
+        self.slots.iter().find(|s| s.name() == plugin_name)
+            .ok_or_else(|| anyhow!("plugin not found: {}", plugin_name))
+    }
+
+    /// Send a command to the named plugin's worker and wait for the reply, bounded by the plugin's call timeout.
+    /// The returned future owns its slot handle, so it can be spawned independently of the manager borrow.
+    fn call<T: Send + 'static>(
+        &self,
+        plugin_name: &str,
+        op: &'static str,
+        make_cmd: impl FnOnce(oneshot::Sender<Result<T>>) -> PluginCmd + Send + 'static,
+    ) -> impl Future<Output = Result<T>> + Send + 'static {
+        call_slot(self.slot(plugin_name).cloned(), op, make_cmd)
+    }
+}
+
+/// A built-in provider's `read_asset`, for host-side servers that hold no `PluginManager`
+#[derive(Clone)]
+pub(crate) struct AssetReader {
+    slot: Arc<PluginSlot>,
+}
+
+impl AssetReader {
+    pub(crate) async fn read(&self, url: &str) -> Result<Vec<u8>> {
+        let url = url.to_string();
+        call_slot(Ok(self.slot.clone()), "ReadAsset", |reply| PluginCmd::ReadAsset { url, reply }).await
+    }
+}
+
+/// Largest image body handed to `transform-asset`
+pub(crate) const MAX_TRANSFORM_BYTES: u64 = 32 * 1024 * 1024;
+
+/// A plugin's `transform-asset` export, for host-side fetchers that hold no `PluginManager`
+#[derive(Clone)]
+pub(crate) struct AssetTransform {
+    slot: Arc<PluginSlot>,
+}
+
+impl AssetTransform {
+    /// Transform the fetched bytes of an image asset; other assets pass through unchanged
+    pub(crate) async fn apply(&self, asset: &Asset, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        if !asset.is_image() {
+            return Ok(bytes);
+        }
+        if bytes.len() as u64 > MAX_TRANSFORM_BYTES {
+            return Err(anyhow!("image of {} bytes is too large to transform", bytes.len()));
+        }
+        let asset = asset.clone();
+        call_slot(Ok(self.slot.clone()), "TransformAsset", |reply| PluginCmd::TransformAsset { asset, bytes, reply }).await
+    }
+}
+
+/// Body of `PluginManager::call` for an already resolved slot
+async fn call_slot<T: Send + 'static>(
+    slot: Result<Arc<PluginSlot>>,
+    op: &'static str,
+    make_cmd: impl FnOnce(oneshot::Sender<Result<T>>) -> PluginCmd + Send + 'static,
+) -> Result<T> {
+    let slot = slot?;
+    let plugin_name = slot.name();
+    let worker = slot.worker().await?;
+    let (reply_tx, reply_rx) = oneshot::channel();
+    worker.tx.send(make_cmd(reply_tx)).await
+        .map_err(|_| anyhow!("plugin worker for {} is not running; failed to send {} command", plugin_name, op))?;
+    match tokio::time::timeout(worker.call_timeout, reply_rx).await {
+        Ok(Ok(res)) => res.with_context(|| format!("plugin {} {} failed", plugin_name, op)),
+        Ok(Err(_)) => Err(anyhow!("plugin worker for {} exited before replying to {}", plugin_name, op)),
+        Err(_) => Err(anyhow!("{} call to plugin {} timed out after {:?}", op, plugin_name, worker.call_timeout)),
+    }
+}
+
+/// Follow `next_cursor` from `first` until the last page, stopping after an error. An empty page or a
+/// cursor seen before also ends the stream, so a misbehaving plugin cannot be polled forever.
+fn paginated<T, F, Fut>(first: PageRequest, fetch: F) -> impl Stream<Item = Result<Page<T>>> + Send + 'static
+where
+    T: Send + 'static,
+    F: Fn(PageRequest) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Page<T>>> + Send + 'static,
+{
+    let seen: HashSet<String> = first.cursor.iter().cloned().collect();
+    stream::unfold((Some(first), fetch, seen), |(next, fetch, mut seen)| async move {
+        let request = next?;
+        match fetch(request.clone()).await {
+            Ok(page) => {
+                let next = page.next_cursor.clone()
+                    .filter(|_| !page.items.is_empty())
+                    .filter(|cursor| seen.insert(cursor.clone()))
+                    .map(|cursor| PageRequest { cursor: Some(cursor), offset: None, limit: request.limit });
+                Some((Ok(page), (next, fetch, seen)))
+            }
+            Err(e) => Some((Err(e), (None, fetch, seen))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    async fn collect(pages: Vec<Page<u32>>) -> Vec<Vec<u32>> {
+        let pages = Arc::new(pages);
+        let stream = paginated(PageRequest::first(2), move |req| {
+            let pages = pages.clone();
+            async move {
+                let i = req.cursor.map_or(0, |c| c.parse::<usize>().unwrap());
+                Ok(pages.get(i).cloned().unwrap_or(Page { items: vec![99], next_cursor: Some(i.to_string()), total: None }))
+            }
+        });
+        stream.map(|page| page.unwrap().items).take(10).collect().await
+    }
+
+    fn page(items: Vec<u32>, next: Option<&str>) -> Page<u32> {
+        Page { items, next_cursor: next.map(str::to_string), total: None }
+    }
+
+    #[tokio::test]
+    async fn paginated_follows_cursors_to_the_last_page() {
+        let pages = vec![page(vec![1, 2], Some("1")), page(vec![3, 4], Some("2")), page(vec![5], None)];
+        assert_eq!(collect(pages).await, vec![vec![1, 2], vec![3, 4], vec![5]]);
+    }
+
+    #[tokio::test]
+    async fn paginated_stops_on_a_repeated_cursor() {
+        let pages = vec![page(vec![1], Some("1")), page(vec![2], Some("1"))];
+        assert_eq!(collect(pages).await, vec![vec![1], vec![2]]);
+    }
+
+    #[tokio::test]
+    async fn paginated_stops_on_an_empty_page() {
+        let pages = vec![page(vec![1], Some("1")), page(vec![], Some("2")), page(vec![3], None)];
+        assert_eq!(collect(pages).await, vec![vec![1], vec![]]);
+    }
+
+    #[tokio::test]
+    async fn paginated_stops_after_an_error() {
+        let stream = paginated(PageRequest::first(1), |_| async { Err::<Page<u32>, _>(anyhow!("boom")) });
+        let results: Vec<Result<Page<u32>>> = stream.collect().await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+}
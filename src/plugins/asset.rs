@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+use crate::plugins::{bindings, AssetKind};
+
+/// Request headers the host never sends on a provider's behalf: `Host` and `Range` are set by the host,
+/// the rest are hop-by-hop or describe the connection rather than the resource
+const DENIED_HEADERS: &[&str] = &[
+    "host",
+    "range",
+    "content-length",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// A header a provider requires on requests for an asset, e.g. a `Referer` expected by an image CDN
+#[derive(ComponentType, Lift, Lower, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct AssetHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl AssetHeader {
+    /// Whether the header is well-formed and not on the host's denylist
+    pub(crate) fn is_allowed(&self) -> bool {
+        HeaderName::from_bytes(self.name.as_bytes()).is_ok()
+            && HeaderValue::from_str(&self.value).is_ok()
+            && !DENIED_HEADERS.iter().any(|d| d.eq_ignore_ascii_case(&self.name))
+    }
+}
+
+/// Generic asset exposed by a unit (page/image/audio/video/subtitle/file links), mirroring `asset` of the
+/// `library-v2` world. Assets from `library` world plugins carry no headers or expiry.
+#[derive(ComponentType, Lift, Lower, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[component(record)]
+pub struct Asset {
+    pub url: String,
+    pub mime: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub kind: AssetKind,
+    /// Headers every request for the asset must carry
+    #[serde(default)]
+    pub headers: Vec<AssetHeader>,
+    /// Unix seconds after which the URL stops working
+    #[serde(default)]
+    #[component(name = "expires-at")]
+    pub expires_at: Option<u64>,
+}
+
+impl Asset {
+    /// Whether the URL has passed its expiry time
+    pub fn is_expired(&self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    /// Whether the asset is a page or image, the assets host-side caching and `transform-asset` apply to
+    pub(crate) fn is_image(&self) -> bool {
+        matches!(self.kind, AssetKind::Page | AssetKind::Image)
+            || self.mime.as_deref().is_some_and(|m| m.starts_with("image/"))
+    }
+}
+
+impl From<bindings::Asset> for Asset {
+    fn from(a: bindings::Asset) -> Self {
+        Self { url: a.url, mime: a.mime, width: a.width, height: a.height, kind: a.kind, headers: Vec::new(), expires_at: None }
+    }
+}
+
+/// Add an asset's required headers to a request
+pub(crate) fn apply_headers(mut req: reqwest::RequestBuilder, headers: &[AssetHeader]) -> reqwest::RequestBuilder {
+    for h in headers.iter().filter(|h| h.is_allowed()) {
+        req = req.header(h.name.as_str(), h.value.as_str());
+    }
+    req
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, value: &str) -> AssetHeader {
+        AssetHeader { name: name.to_string(), value: value.to_string() }
+    }
+
+    #[test]
+    fn ordinary_headers_are_allowed() {
+        assert!(header("Referer", "https://site.test/").is_allowed());
+        assert!(header("x-custom-token", "abc").is_allowed());
+        assert!(header("Cookie", "a=b").is_allowed());
+    }
+
+    #[test]
+    fn denied_headers_are_rejected_regardless_of_case() {
+        for name in ["Host", "HOST", "range", "Content-Length", "Transfer-Encoding", "Proxy-Authorization", "TE"] {
+            assert!(!header(name, "x").is_allowed(), "{} should be denied", name);
+        }
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        assert!(!header("", "x").is_allowed());
+        assert!(!header("Bad Name", "x").is_allowed());
+        assert!(!header(" Host", "x").is_allowed());
+        assert!(!header("Referer", "line\r\nInjected: 1").is_allowed());
+    }
+
+    #[test]
+    fn expiry() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let asset = |expires_at| Asset {
+            url: "https://cdn.test/1.jpg".to_string(),
+            mime: None,
+            width: None,
+            height: None,
+            kind: AssetKind::Page,
+            headers: Vec::new(),
+            expires_at,
+        };
+        assert!(!asset(None).is_expired());
+        assert!(asset(Some(now - 1)).is_expired());
+        assert!(!asset(Some(now + 3600)).is_expired());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+/// Content-addressed on-disk cache of serialized components.
+/// Entries are named `<sha256 of wasm>-<engine key>.cwasm`, where the engine key covers the
+/// target, compiler flags, enabled features and wasmtime version, so a changed engine never
+/// picks up an incompatible entry.
+pub(crate) struct CompileCache {
+    dir: PathBuf,
+    engine_key: String,
+}
+
+impl CompileCache {
+    /// Open (creating if needed) the cache directory and drop entries built by other engine configurations
+    pub(crate) fn new(engine: &Engine, dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("failed to create compile cache dir {}: {}", dir.display(), e))?;
+        let mut hasher = DefaultHasher::new();
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let cache = Self { dir: dir.to_path_buf(), engine_key: format!("{:016x}", hasher.finish()) };
+        cache.prune_stale();
+        Ok(cache)
+    }
+
+    /// Load the component for a `.wasm` file from the cache, compiling and storing it on a miss.
+    /// Returns the component and whether it came from the cache.
+    pub(crate) fn load_or_compile(&self, engine: &Engine, wasm_path: &Path) -> Result<(Component, bool)> {
+        let bytes = std::fs::read(wasm_path)?;
+        let entry = self.entry_path(&bytes);
+        if entry.exists() {
+            // SAFETY: entries are only written by `store` from `Component::serialize` and are keyed by
+            // the engine's compatibility hash; wasmtime re-validates the header before mapping code.
+            match unsafe { Component::deserialize_file(engine, &entry) } {
+                Ok(component) => {
+                    debug!(path=%wasm_path.display(), entry=%entry.display(), "compile cache hit");
+                    return Ok((component, true));
+                }
+                Err(e) => {
+                    warn!(entry=%entry.display(), error=%e, "discarding unusable compile cache entry");
+                    let _ = std::fs::remove_file(&entry);
+                }
+            }
+        }
+        let component = Component::from_binary(engine, &bytes)?;
+        self.store(&entry, &component);
+        Ok((component, false))
+    }
+
+    /// Compile a `.wasm` file into the cache if it is not already present. Returns true if it was compiled.
+    pub(crate) fn precompile(&self, engine: &Engine, wasm_path: &Path) -> Result<bool> {
+        let bytes = std::fs::read(wasm_path)?;
+        let entry = self.entry_path(&bytes);
+        if entry.exists() {
+            return Ok(false);
+        }
+        let serialized = engine.precompile_component(&bytes)?;
+        write_atomic(&entry, &serialized)?;
+        info!(path=%wasm_path.display(), entry=%entry.display(), "precompiled plugin into compile cache");
+        Ok(true)
+    }
+
+    fn entry_path(&self, wasm: &[u8]) -> PathBuf {
+        let digest = Sha256::digest(wasm);
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}-{}.cwasm", hex, self.engine_key))
+    }
+
+    fn store(&self, entry: &Path, component: &Component) {
+        let res = component.serialize().and_then(|bytes| write_atomic(entry, &bytes));
+        match res {
+            Ok(()) => debug!(entry=%entry.display(), "stored compile cache entry"),
+            Err(e) => warn!(entry=%entry.display(), error=%e, "failed to store compile cache entry"),
+        }
+    }
+
+    fn prune_stale(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let suffix = format!("-{}.cwasm", self.engine_key);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if name.ends_with(".cwasm") && !name.ends_with(&suffix) {
+                debug!(entry=%path.display(), "removing compile cache entry from another engine configuration");
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+}
+
+/// Distinguishes temporary files of concurrent writes within this process
+/// Write via a temporary file and rename so readers never observe a partial entry.
+/// Every call gets its own temporary file, so concurrent compiles of one artifact cannot interleave.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = crate::tmp::sibling(path);
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        anyhow!("failed to move {} into place: {}", path.display(), e)
+    })
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -8,4 +9,15 @@ pub(crate) struct PluginConfig {
     pub(crate) rate_limit_ms: Option<u64>,
     #[serde(default)]
     pub(crate) call_timeout_ms: Option<u64>,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub(crate) max_memory_mb: Option<u64>,
+    #[serde(default)]
+    pub(crate) max_tables: Option<usize>,
+    #[serde(default)]
+    pub(crate) max_instances: Option<usize>,
+    #[serde(default)]
+    pub(crate) max_table_elements: Option<usize>,
+    /// Extra environment variables exposed to the plugin; the host environment is never inherited
+    #[serde(default)]
+    pub(crate) env: Option<HashMap<String, String>>,
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+use crate::plugins::search::{ContentRating, PublicationStatus};
+use crate::plugins::Media;
+
+#[derive(ComponentType, Lift, Lower, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[component(variant)]
+pub enum CreditRole {
+    #[component(name = "author")]
+    Author,
+    #[component(name = "artist")]
+    Artist,
+    #[component(name = "studio")]
+    Studio,
+    #[component(name = "other")]
+    Other(String),
+}
+
+#[derive(ComponentType, Lift, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct Credit {
+    pub name: String,
+    pub role: CreditRole,
+}
+
+#[derive(ComponentType, Lift, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct AltTitle {
+    pub title: String,
+    /// ISO language code of the title, if known
+    pub lang: Option<String>,
+}
+
+/// Identifier of the same work on another service
+#[derive(ComponentType, Lift, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct ExternalId {
+    /// Service name, e.g. "anilist" or "myanimelist"
+    pub source: String,
+    pub id: String,
+}
+
+/// Extended metadata of a media item, mirroring `media-details` of the `library-v2` world
+#[derive(ComponentType, Lift, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[component(record)]
+pub struct MediaDetails {
+    pub media: Media,
+    /// Authors, artists and studios
+    pub credits: Vec<Credit>,
+    /// Genres and tags
+    pub tags: Vec<String>,
+    pub status: Option<PublicationStatus>,
+    #[component(name = "alt-titles")]
+    pub alt_titles: Vec<AltTitle>,
+    #[component(name = "content-rating")]
+    pub content_rating: Option<ContentRating>,
+    /// Average score out of 10
+    pub rating: Option<f32>,
+    /// Year of first release
+    pub year: Option<u32>,
+    #[component(name = "banner-url")]
+    pub banner_url: Option<String>,
+    #[component(name = "external-ids")]
+    pub external_ids: Vec<ExternalId>,
+}
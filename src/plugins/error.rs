@@ -0,0 +1,76 @@
+use std::fmt;
+use std::time::Duration;
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+/// Distinct plugin failures callers may want to handle, recoverable via `anyhow::Error::downcast_ref`
+#[derive(Debug, Clone)]
+pub enum PluginError {
+    /// The plugin tried to grow memory or tables (or create instances) beyond its configured limits
+    ResourceLimitExceeded { plugin: String, detail: String },
+    /// The plugin reported a failure through the `library-v2` world
+    Provider { plugin: String, error: ProviderError },
+    /// The request uses something the plugin does not advertise; it was not sent
+    InvalidRequest { plugin: String, reason: String },
+}
+
+impl PluginError {
+    /// How long the provider asked callers to back off, if it reported rate limiting
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            PluginError::Provider { error: ProviderError::RateLimited(Some(secs)), .. } => {
+                Some(Duration::from_secs(u64::from(*secs)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::ResourceLimitExceeded { plugin, detail } => {
+                write!(f, "plugin {} resource limit exceeded: {}", plugin, detail)
+            }
+            PluginError::Provider { plugin, error } => write!(f, "plugin {}: {}", plugin, error),
+            PluginError::InvalidRequest { plugin, reason } => write!(f, "invalid request for plugin {}: {}", plugin, reason),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Failure reported by a provider, mirroring the `provider-error` variant of the `library-v2` world
+#[derive(ComponentType, Lift, Lower, Debug, Clone, PartialEq, Eq)]
+#[component(variant)]
+pub enum ProviderError {
+    /// The upstream service could not be reached or answered with an unexpected status
+    #[component(name = "network")]
+    Network(String),
+    #[component(name = "not-found")]
+    NotFound,
+    /// Retry after this many seconds, if the provider knows
+    #[component(name = "rate-limited")]
+    RateLimited(Option<u32>),
+    #[component(name = "auth-required")]
+    AuthRequired,
+    /// The upstream response could not be understood
+    #[component(name = "parse-failure")]
+    ParseFailure(String),
+    /// The provider does not support this operation or media type
+    #[component(name = "unsupported")]
+    Unsupported,
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Network(detail) => write!(f, "network error: {}", detail),
+            ProviderError::NotFound => write!(f, "not found"),
+            ProviderError::RateLimited(Some(secs)) => write!(f, "rate limited, retry after {}s", secs),
+            ProviderError::RateLimited(None) => write!(f, "rate limited"),
+            ProviderError::AuthRequired => write!(f, "authentication required"),
+            ProviderError::ParseFailure(detail) => write!(f, "failed to parse response: {}", detail),
+            ProviderError::Unsupported => write!(f, "unsupported"),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+use crate::plugins::MediaType;
+
+#[derive(ComponentType, Lift, Lower, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[component(variant)]
+pub enum FeedKind {
+    #[component(name = "latest")]
+    Latest,
+    #[component(name = "popular")]
+    Popular,
+    #[component(name = "featured")]
+    Featured,
+    #[component(name = "other")]
+    Other(String),
+}
+
+/// A named browse list offered by a provider, mirroring `feed` of the `library-v2` world
+#[derive(ComponentType, Lift, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[component(record)]
+pub struct Feed {
+    /// Identifier passed to `fetch_feed`
+    pub id: String,
+    pub title: String,
+    pub kind: FeedKind,
+    /// Media type of the feed's items when they all share one
+    #[component(name = "media-type")]
+    pub media_type: Option<MediaType>,
+}
@@ -1,11 +1,23 @@
+use tracing::warn;
+use wasmtime::{ResourceLimiter, StoreLimits};
 use wasmtime_wasi::{WasiCtx, WasiCtxView, WasiView};
-use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::{default_send_request, HostFutureIncomingResponse, OutgoingRequestConfig};
+use wasmtime_wasi_http::{HttpResult, WasiHttpCtx, WasiHttpView};
+
+use crate::plugins::hosts::HostPolicy;
 
 /// WASMTime Host environment for plugins
 pub(crate) struct Host {
+    pub(crate) name: String,
     pub(crate) wasi: WasiCtx,
     pub(crate) table: wasmtime_wasi::ResourceTable,
     pub(crate) http: WasiHttpCtx,
+    pub(crate) hosts: HostPolicy,
+    pub(crate) limits: StoreLimits,
+    /// Set when the limiter denies a request, so the failing call can be reported as a limit error
+    pub(crate) limit_exceeded: Option<String>,
 }
 
 /// Implement the necessary traits for the Host struct
@@ -26,4 +38,65 @@ impl WasiHttpView for Host {
     fn table(&mut self) -> &mut wasmtime_wasi::ResourceTable {
         &mut self.table
     }
-}
\ No newline at end of file
+
+    /// Check every outgoing request against the plugin's allowed hosts before any connection is made
+    fn send_request(
+        &mut self,
+        request: hyper::Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HttpResult<HostFutureIncomingResponse> {
+        let uri = request.uri();
+        let scheme = uri.scheme_str().unwrap_or(if config.use_tls { "https" } else { "http" });
+        let Some(host) = uri.host() else {
+            warn!(plugin=%self.name, %uri, "denied outgoing HTTP request without host");
+            return Err(ErrorCode::HttpRequestUriInvalid.into());
+        };
+        let port = uri.port_u16().unwrap_or(if config.use_tls { 443 } else { 80 });
+        if !self.hosts.allows(scheme, host, Some(port)) {
+            warn!(plugin=%self.name, %uri, "denied outgoing HTTP request to host outside allowed_hosts");
+            return Err(ErrorCode::HttpRequestDenied.into());
+        }
+        Ok(default_send_request(request, config))
+    }
+}
+
+/// Enforce the plugin's StoreLimits, remembering the first denial for error reporting
+impl ResourceLimiter for Host {
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> anyhow::Result<bool> {
+        let res = self.limits.memory_growing(current, desired, maximum);
+        if !matches!(res, Ok(true)) && self.limit_exceeded.is_none() {
+            warn!(plugin=%self.name, current, desired, "plugin memory growth denied");
+            self.limit_exceeded = Some(format!("memory growth to {} bytes denied", desired));
+        }
+        res
+    }
+
+    fn memory_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> anyhow::Result<bool> {
+        let res = self.limits.table_growing(current, desired, maximum);
+        if !matches!(res, Ok(true)) && self.limit_exceeded.is_none() {
+            warn!(plugin=%self.name, current, desired, "plugin table growth denied");
+            self.limit_exceeded = Some(format!("table growth to {} elements denied", desired));
+        }
+        res
+    }
+
+    fn table_grow_failed(&mut self, error: anyhow::Error) -> anyhow::Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
@@ -0,0 +1,166 @@
+use url::Url;
+
+/// A single `allowed_hosts` entry: `[scheme://]host[:port]`, where host may be a `*.` wildcard
+#[derive(Debug, Clone)]
+struct HostRule {
+    scheme: Option<String>,
+    host: String,
+    port: Option<u16>,
+}
+
+impl HostRule {
+    /// Parse a rule from a normalized (trimmed, lowercase) config entry
+    fn parse(entry: &str) -> Option<Self> {
+        let (scheme, rest) = match entry.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_string()), rest),
+            None => (None, entry),
+        };
+        let rest = rest.trim_end_matches('/');
+        let (host, port) = match rest.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, Some(port.parse::<u16>().ok()?)),
+            _ => (rest, None),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(Self { scheme, host: host.to_string(), port })
+    }
+
+    fn matches(&self, scheme: &str, host: &str, port: Option<u16>) -> bool {
+        if let Some(s) = &self.scheme {
+            if s != scheme {
+                return false;
+            }
+        }
+        if let Some(p) = self.port {
+            if port != Some(p) {
+                return false;
+            }
+        }
+        if let Some(stripped) = self.host.strip_prefix("*.") {
+            host == stripped || host.ends_with(&format!(".{}", stripped))
+        } else {
+            host == self.host
+        }
+    }
+}
+
+/// Host allow-list derived from a plugin's `allowed_hosts` config.
+/// `None` allows every host, an empty list denies every host.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostPolicy {
+    rules: Option<Vec<HostRule>>,
+}
+
+impl HostPolicy {
+    pub(crate) fn new(allowed_hosts: Option<&[String]>) -> Self {
+        let rules = allowed_hosts.map(|list| list.iter().filter_map(|h| HostRule::parse(h)).collect());
+        Self { rules }
+    }
+
+    /// Whether the given URL may be contacted or handed out to callers
+    pub(crate) fn allows_url(&self, url: &str) -> bool {
+        if self.rules.is_none() {
+            return true;
+        }
+        let Ok(parsed) = Url::parse(url) else {
+            return false;
+        };
+        let Some(host) = parsed.host_str() else {
+            return false;
+        };
+        self.allows(parsed.scheme(), host, parsed.port_or_known_default())
+    }
+
+    /// Whether a request to `scheme://host:port` is permitted. Only http and https are ever allowed.
+    pub(crate) fn allows(&self, scheme: &str, host: &str, port: Option<u16>) -> bool {
+        let Some(rules) = &self.rules else {
+            return true;
+        };
+        let scheme = scheme.to_ascii_lowercase();
+        if scheme != "http" && scheme != "https" {
+            return false;
+        }
+        let host = host.to_ascii_lowercase();
+        rules.iter().any(|rule| rule.matches(&scheme, &host, port))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(hosts: &[&str]) -> HostPolicy {
+        let hosts: Vec<String> = hosts.iter().map(|h| h.to_string()).collect();
+        HostPolicy::new(Some(&hosts))
+    }
+
+    #[test]
+    fn no_list_allows_everything_and_empty_list_denies_everything() {
+        assert!(HostPolicy::new(None).allows_url("https://anything.test/x"));
+        assert!(!policy(&[]).allows_url("https://example.com/"));
+    }
+
+    #[test]
+    fn exact_host() {
+        let p = policy(&["example.com"]);
+        assert!(p.allows_url("https://example.com/a"));
+        assert!(p.allows_url("http://EXAMPLE.com/a"));
+        assert!(!p.allows_url("https://cdn.example.com/a"));
+        assert!(!p.allows_url("https://example.com.evil.test/a"));
+        assert!(!p.allows_url("https://notexample.com/a"));
+    }
+
+    #[test]
+    fn wildcard_matches_subdomains_and_apex() {
+        let p = policy(&["*.example.com"]);
+        assert!(p.allows_url("https://example.com/"));
+        assert!(p.allows_url("https://cdn.example.com/"));
+        assert!(p.allows_url("https://a.b.example.com/"));
+        assert!(!p.allows_url("https://badexample.com/"));
+        assert!(!p.allows_url("https://example.com.evil.test/"));
+    }
+
+    #[test]
+    fn explicit_port() {
+        let p = policy(&["example.com:8443"]);
+        assert!(p.allows_url("https://example.com:8443/"));
+        assert!(!p.allows_url("https://example.com/"));
+        assert!(!p.allows_url("http://example.com:8080/"));
+        // Default ports are compared like explicit ones
+        let p = policy(&["example.com:443"]);
+        assert!(p.allows_url("https://example.com/"));
+        assert!(!p.allows_url("http://example.com/"));
+    }
+
+    #[test]
+    fn scheme_restriction() {
+        let p = policy(&["https://example.com/"]);
+        assert!(p.allows_url("https://example.com/a"));
+        assert!(!p.allows_url("http://example.com/a"));
+    }
+
+    #[test]
+    fn only_http_schemes_pass_a_list() {
+        let p = policy(&["example.com"]);
+        assert!(!p.allows_url("ftp://example.com/a"));
+        assert!(!p.allows_url("file:///etc/passwd"));
+        assert!(!p.allows_url("not a url"));
+        assert!(!p.allows("ws", "example.com", Some(80)));
+    }
+
+    #[test]
+    fn ipv6_hosts() {
+        let p = policy(&["[::1]:8080"]);
+        assert!(p.allows_url("http://[::1]:8080/"));
+        assert!(!p.allows_url("http://[::1]:9090/"));
+        assert!(policy(&["[::1]"]).allows_url("http://[::1]:9090/"));
+    }
+
+    #[test]
+    fn invalid_entries_are_ignored() {
+        let p = policy(&["", "example.com:notaport", ":80", "ok.test"]);
+        assert!(!p.allows_url("https://example.com/"));
+        assert!(p.allows_url("https://ok.test/"));
+    }
+}
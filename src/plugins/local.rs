@@ -0,0 +1,420 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use url::Url;
+
+use crate::plugins::native::NativeProvider;
+use crate::plugins::{Asset, AssetKind, Media, MediaType, PluginError, MAX_TRANSFORM_BYTES, ProviderCapabilities, ProviderError, Unit, UnitKind};
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "avif"];
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mkv", "webm", "avi", "mov", "m4v"];
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "vtt", "ass"];
+/// Names that mark a cover image inside a series directory
+const COVER_NAMES: &[&str] = &["cover", "folder", "poster"];
+
+/// Kinds of local files the provider understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalFormat {
+    Cbz,
+    Cbr,
+    Epub,
+    Pdf,
+    Video,
+    /// Directory of loose page images
+    ImageDir,
+}
+
+impl LocalFormat {
+    fn of(path: &Path) -> Option<Self> {
+        if path.is_dir() {
+            return has_images(path).then_some(LocalFormat::ImageDir);
+        }
+        let ext = extension(path)?;
+        match ext.as_str() {
+            "cbz" => Some(LocalFormat::Cbz),
+            "cbr" => Some(LocalFormat::Cbr),
+            "epub" => Some(LocalFormat::Epub),
+            "pdf" => Some(LocalFormat::Pdf),
+            e if VIDEO_EXTENSIONS.contains(&e) => Some(LocalFormat::Video),
+            _ => None,
+        }
+    }
+
+    fn media_type(&self) -> MediaType {
+        match self {
+            LocalFormat::Video => MediaType::Video,
+            _ => MediaType::Paged,
+        }
+    }
+}
+
+/// Built-in provider serving a directory of local files.
+///
+/// Each top-level file or directory is a media item. A directory's units are the supported files and image
+/// folders inside it; a single file is its own unit. Ids are paths relative to the library root. Pages inside
+/// a CBZ are addressed by the archive's `file://` URL with the entry name as fragment; since no URL loader
+/// understands that form, their contents are served by `read_asset`.
+pub(crate) struct LocalLibrary {
+    root: PathBuf,
+}
+
+impl LocalLibrary {
+    pub(crate) fn new(root: &Path) -> Self {
+        Self { root: root.to_path_buf() }
+    }
+
+    /// Map an id back to a path. Ids that escape the library root or name missing files are not found.
+    fn resolve(&self, id: &str) -> Result<PathBuf> {
+        let rel = Path::new(id);
+        let path = self.root.join(rel);
+        if id.is_empty() || !rel.components().all(|c| matches!(c, Component::Normal(_))) || !path.exists() {
+            return Err(PluginError::Provider { plugin: self.name().to_string(), error: ProviderError::NotFound }.into());
+        }
+        Ok(path)
+    }
+
+    fn id_of(&self, path: &Path) -> String {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        rel.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Media item for a top-level entry, if it holds anything the provider can serve
+    fn media_for(&self, path: &Path) -> Option<Media> {
+        let mediatype = match LocalFormat::of(path) {
+            Some(format) => format.media_type(),
+            None if path.is_dir() => {
+                let formats: Vec<LocalFormat> = list_dir(path).ok()?.iter().filter_map(|p| LocalFormat::of(p)).collect();
+                if formats.is_empty() {
+                    return None;
+                }
+                if formats.contains(&LocalFormat::Video) { MediaType::Video } else { MediaType::Paged }
+            }
+            None => return None,
+        };
+        let cover_url = path.is_dir()
+            .then(|| list_dir(path).ok())
+            .flatten()
+            .and_then(|entries| entries.into_iter().find(|p| is_cover(p)))
+            .and_then(|p| file_url(&p));
+        Some(Media {
+            id: self.id_of(path),
+            mediatype,
+            title: title_of(path),
+            description: None,
+            url: file_url(path),
+            cover_url,
+        })
+    }
+
+    fn unit_for(&self, path: &Path, format: LocalFormat) -> Unit {
+        let title = title_of(path);
+        let number = parse_number(&title);
+        Unit {
+            id: self.id_of(path),
+            number_text: number.as_ref().map(|(text, _)| text.clone()),
+            number: number.map(|(_, n)| n),
+            title,
+            lang: None,
+            group: None,
+            url: file_url(path),
+            published_at: None,
+            kind: if format == LocalFormat::Video { UnitKind::Episode } else { UnitKind::Chapter },
+            upload_group: None,
+        }
+    }
+}
+
+impl NativeProvider for LocalLibrary {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn location(&self) -> PathBuf {
+        self.root.clone()
+    }
+
+    fn capabilities(&self) -> Result<ProviderCapabilities> {
+        Ok(ProviderCapabilities {
+            media_types: vec![MediaType::Paged, MediaType::Video],
+            unit_kinds: vec![UnitKind::Chapter, UnitKind::Episode],
+            asset_kinds: vec![AssetKind::Page, AssetKind::Video, AssetKind::Subtitle, AssetKind::File],
+        })
+    }
+
+    /// Top-level entries of the given kind whose title contains the query (case-insensitive)
+    fn fetch_media_list(&self, kind: MediaType, query: &str) -> Result<Vec<Media>> {
+        let query = query.trim().to_lowercase();
+        Ok(list_dir(&self.root)?
+            .iter()
+            .filter_map(|p| self.media_for(p))
+            .filter(|m| m.mediatype == kind)
+            .filter(|m| query.is_empty() || m.title.to_lowercase().contains(&query))
+            .collect())
+    }
+
+    fn fetch_units(&self, media_id: &str) -> Result<Vec<Unit>> {
+        let path = self.resolve(media_id)?;
+        if let Some(format) = LocalFormat::of(&path) {
+            return Ok(vec![self.unit_for(&path, format)]);
+        }
+        Ok(list_dir(&path)?
+            .iter()
+            .filter_map(|p| LocalFormat::of(p).map(|format| self.unit_for(p, format)))
+            .collect())
+    }
+
+    fn fetch_assets(&self, unit_id: &str) -> Result<Vec<Asset>> {
+        let path = self.resolve(unit_id)?;
+        let format = LocalFormat::of(&path).ok_or_else(|| PluginError::Provider {
+            plugin: self.name().to_string(),
+            error: ProviderError::Unsupported,
+        })?;
+        let url = file_url(&path).ok_or_else(|| anyhow!("cannot build a URL for {}", path.display()))?;
+        let assets = match format {
+            LocalFormat::Cbz => cbz_pages(&path, &url)?,
+            LocalFormat::ImageDir => list_dir(&path)?
+                .iter()
+                .filter(|p| is_image(p))
+                .filter_map(|p| Some(page_asset(file_url(p)?, p.to_string_lossy().as_ref())))
+                .collect(),
+            LocalFormat::Video => {
+                let mut assets = vec![asset(url, mime_for(&path), AssetKind::Video)];
+                assets.extend(subtitles_for(&path));
+                assets
+            }
+            LocalFormat::Cbr => vec![asset(url, Some("application/vnd.comicbook-rar"), AssetKind::File)],
+            LocalFormat::Epub => vec![asset(url, Some("application/epub+zip"), AssetKind::File)],
+            LocalFormat::Pdf => vec![asset(url, Some("application/pdf"), AssetKind::File)],
+        };
+        Ok(assets)
+    }
+
+    /// Contents of an image under the library root, or of an image entry inside a CBZ when the URL has a
+    /// fragment. Reads stop at `MAX_TRANSFORM_BYTES`, whatever size the file or archive claims.
+    fn read_asset(&self, url: &str) -> Result<Vec<u8>> {
+        let not_found = || PluginError::Provider { plugin: self.name().to_string(), error: ProviderError::NotFound };
+        let not_image = || PluginError::InvalidRequest { plugin: self.name().to_string(), reason: "only images are served".to_string() };
+        let parsed = Url::parse(url)?;
+        if parsed.scheme() != "file" {
+            return Err(not_found().into());
+        }
+        let path = parsed.to_file_path().map_err(|_| not_found())?;
+        let root = self.root.canonicalize()?;
+        let path = path.canonicalize().map_err(|_| not_found())?;
+        if !path.starts_with(&root) || !path.is_file() {
+            return Err(not_found().into());
+        }
+        let Some(fragment) = parsed.fragment() else {
+            if !is_image(&path) {
+                return Err(not_image().into());
+            }
+            let file = File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+            return read_capped(file, url);
+        };
+        if LocalFormat::of(&path) != Some(LocalFormat::Cbz) {
+            return Err(not_found().into());
+        }
+        let file = File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut archive = zip::ZipArchive::new(file).with_context(|| format!("failed to read {}", path.display()))?;
+        // Compare in URL form, so entry names need not be percent-decoded
+        let mut probe = parsed.clone();
+        let name = archive.file_names()
+            .find(|name| {
+                probe.set_fragment(Some(name));
+                probe.fragment() == Some(fragment)
+            })
+            .map(str::to_string)
+            .ok_or_else(not_found)?;
+        if !is_image(Path::new(&name)) {
+            return Err(not_image().into());
+        }
+        let entry = archive.by_name(&name)?;
+        read_capped(entry, url)
+    }
+}
+
+/// Read at most `MAX_TRANSFORM_BYTES`, failing rather than truncating larger contents
+fn read_capped(reader: impl Read, url: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(MAX_TRANSFORM_BYTES + 1).read_to_end(&mut bytes)
+        .with_context(|| format!("failed to read {}", url))?;
+    if bytes.len() as u64 > MAX_TRANSFORM_BYTES {
+        return Err(anyhow!("{} is larger than {} bytes", url, MAX_TRANSFORM_BYTES));
+    }
+    Ok(bytes)
+}
+
+/// Image entries of a CBZ in natural order, addressed as `<archive url>#<entry>`
+fn cbz_pages(path: &Path, url: &str) -> Result<Vec<Asset>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let archive = zip::ZipArchive::new(file).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut names: Vec<&str> = archive.file_names()
+        .filter(|n| !n.ends_with('/') && !n.starts_with("__MACOSX"))
+        .filter(|n| is_image(Path::new(n)))
+        .collect();
+    names.sort_by(|a, b| natural_cmp(a, b));
+    let base = Url::parse(url)?;
+    Ok(names.into_iter()
+        .map(|name| {
+            let mut entry = base.clone();
+            entry.set_fragment(Some(name));
+            page_asset(entry.to_string(), name)
+        })
+        .collect())
+}
+
+/// Sidecar subtitle files sharing the video's file stem
+fn subtitles_for(video: &Path) -> Vec<Asset> {
+    let (Some(dir), Some(stem)) = (video.parent(), video.file_stem().and_then(|s| s.to_str())) else {
+        return Vec::new();
+    };
+    list_dir(dir).unwrap_or_default()
+        .into_iter()
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(stem) && p != video))
+        .filter(|p| extension(p).is_some_and(|e| SUBTITLE_EXTENSIONS.contains(&e.as_str())))
+        .filter_map(|p| Some(asset(file_url(&p)?, mime_for(&p), AssetKind::Subtitle)))
+        .collect()
+}
+
+fn asset(url: String, mime: Option<&str>, kind: AssetKind) -> Asset {
+    Asset { url, mime: mime.map(str::to_string), width: None, height: None, kind, headers: Vec::new(), expires_at: None }
+}
+
+fn page_asset(url: String, name: &str) -> Asset {
+    asset(url, mime_for(Path::new(name)), AssetKind::Page)
+}
+
+fn mime_for(path: &Path) -> Option<&'static str> {
+    let mime = match extension(path)?.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "avif" => "image/avif",
+        "mp4" | "m4v" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "mov" => "video/quicktime",
+        "srt" => "application/x-subrip",
+        "vtt" => "text/vtt",
+        "ass" => "text/x-ssa",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase())
+}
+
+fn is_image(path: &Path) -> bool {
+    extension(path).is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.as_str()))
+}
+
+fn is_cover(path: &Path) -> bool {
+    is_image(path)
+        && path.file_stem().and_then(|s| s.to_str()).is_some_and(|s| COVER_NAMES.contains(&s.to_ascii_lowercase().as_str()))
+}
+
+fn has_images(dir: &Path) -> bool {
+    list_dir(dir).is_ok_and(|entries| entries.iter().any(|p| is_image(p) && !is_cover(p)))
+}
+
+fn title_of(path: &Path) -> String {
+    let name = if path.is_dir() { path.file_name() } else { path.file_stem() };
+    name.map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn file_url(path: &Path) -> Option<String> {
+    Url::from_file_path(path).ok().map(|u| u.to_string())
+}
+
+/// Visible directory entries in natural name order
+fn list_dir(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| !p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')))
+        .collect();
+    entries.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+    Ok(entries)
+}
+
+/// Compare names so that embedded numbers sort by value ("2" before "10")
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x: String = std::iter::from_fn(|| a.next_if(|c| c.is_ascii_digit())).collect();
+                let y: String = std::iter::from_fn(|| b.next_if(|c| c.is_ascii_digit())).collect();
+                let (xt, yt) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ord = xt.len().cmp(&yt.len()).then_with(|| xt.cmp(yt));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Unit number from a file name: the number after a chapter/episode marker ("Ch.12", "S01E05", "#3"),
+/// otherwise the first number in the name
+fn parse_number(title: &str) -> Option<(String, f32)> {
+    let lower = title.to_ascii_lowercase();
+    for marker in ["chapter", "episode", "ch", "ep", "e", "#"] {
+        for (idx, _) in lower.match_indices(marker) {
+            let before = lower[..idx].chars().next_back();
+            let boundary = match marker {
+                // Only as in "S01E05"
+                "e" => before.is_some_and(|c| c.is_ascii_digit()),
+                _ => !before.is_some_and(|c| c.is_ascii_alphabetic()),
+            };
+            if !boundary {
+                continue;
+            }
+            let rest = lower[idx + marker.len()..].trim_start_matches(['.', ' ', '_', '-']);
+            if let Some(found) = leading_number(rest) {
+                return Some(found);
+            }
+        }
+    }
+    let start = lower.find(|c: char| c.is_ascii_digit())?;
+    leading_number(&lower[start..])
+}
+
+fn leading_number(s: &str) -> Option<(String, f32)> {
+    let int_len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if int_len == 0 {
+        return None;
+    }
+    let mut len = int_len;
+    if let Some(frac) = s[int_len..].strip_prefix('.') {
+        let frac_len = frac.find(|c: char| !c.is_ascii_digit()).unwrap_or(frac.len());
+        if frac_len > 0 {
+            len += 1 + frac_len;
+        }
+    }
+    let text = &s[..len];
+    let number = text.parse().ok()?;
+    let text = text.trim_start_matches('0');
+    let text = if text.is_empty() || text.starts_with('.') { format!("0{}", text) } else { text.to_string() };
+    Some((text, number))
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::plugins::hosts::HostPolicy;
+use crate::plugins::paging::{paginate, Page, PageRequest};
+use crate::plugins::details::MediaDetails;
+use crate::plugins::feeds::Feed;
+use crate::plugins::search::{check_request, SearchCapabilities, SearchRequest};
+use crate::plugins::{ArtifactKind, Asset, Media, MediaType, PluginCmd, PluginError, PluginWorker, ProviderCapabilities, Unit};
+
+/// Call timeout for built-in providers, which only touch local resources
+const NATIVE_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A provider implemented in Rust that serves the same operations as a `library` world plugin.
+/// Methods may block; they run on the provider's own worker thread.
+pub(crate) trait NativeProvider: Send + Sync + 'static {
+    fn name(&self) -> &str;
+    /// Location the provider serves, reported as its artifact path
+    fn location(&self) -> PathBuf;
+    fn capabilities(&self) -> Result<ProviderCapabilities>;
+    fn fetch_media_list(&self, kind: MediaType, query: &str) -> Result<Vec<Media>>;
+    fn fetch_units(&self, media_id: &str) -> Result<Vec<Unit>>;
+    fn fetch_assets(&self, unit_id: &str) -> Result<Vec<Asset>>;
+
+    fn fetch_media_details(&self, _media_id: &str) -> Result<MediaDetails> {
+        Err(PluginError::InvalidRequest { plugin: self.name().to_string(), reason: "media details are not supported".to_string() }.into())
+    }
+
+    fn fetch_media_page(&self, kind: MediaType, query: &str, page: &PageRequest) -> Result<Page<Media>> {
+        Ok(paginate(&self.fetch_media_list(kind, query)?, page))
+    }
+
+    fn fetch_units_page(&self, media_id: &str, page: &PageRequest) -> Result<Page<Unit>> {
+        Ok(paginate(&self.fetch_units(media_id)?, page))
+    }
+
+    /// Search filters the provider honours; None accepts plain requests only
+    fn search_capabilities(&self) -> Option<SearchCapabilities> {
+        None
+    }
+
+    /// Run a search that already passed validation against `search_capabilities`
+    fn search(&self, request: &SearchRequest, page: &PageRequest) -> Result<Page<Media>> {
+        self.fetch_media_page(request.kind.clone(), &request.query, page)
+    }
+
+    fn list_feeds(&self) -> Result<Vec<Feed>> {
+        Ok(Vec::new())
+    }
+
+    fn fetch_feed(&self, _feed_id: &str, _cursor: Option<String>) -> Result<Page<Media>> {
+        Err(PluginError::InvalidRequest { plugin: self.name().to_string(), reason: "feeds are not supported".to_string() }.into())
+    }
+
+    /// Contents of an asset handed out by this provider
+    fn read_asset(&self, _url: &str) -> Result<Vec<u8>> {
+        Err(PluginError::InvalidRequest { plugin: self.name().to_string(), reason: "reading assets is not supported".to_string() }.into())
+    }
+
+    /// Whether fetched images must be passed through `transform_asset`
+    fn transforms_assets(&self) -> bool {
+        false
+    }
+
+    fn transform_asset(&self, _asset: &Asset, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(bytes)
+    }
+
+    /// Hosts the host may contact on behalf of this provider; none by default
+    fn allowed_hosts(&self) -> Option<Vec<String>> {
+        Some(Vec::new())
+    }
+}
+
+/// Serve plugin commands for a native provider on a dedicated thread
+pub(crate) fn spawn_worker(provider: Arc<dyn NativeProvider>) -> PluginWorker {
+    let artifact_path = provider.location();
+    let (tx, mut rx) = mpsc::channel::<PluginCmd>(64);
+    std::thread::spawn(move || {
+        while let Some(cmd) = rx.blocking_recv() {
+            // Same as the wasm worker: skip work whose caller already gave up
+            if cmd.is_abandoned() {
+                debug!(plugin=%provider.name(), op=cmd.op_name(), "skipping abandoned command");
+                continue;
+            }
+            match cmd {
+                PluginCmd::FetchMediaList { kind, query, reply } => {
+                    let _ = reply.send(provider.fetch_media_list(kind, &query));
+                }
+                PluginCmd::FetchUnits { media_id, reply } => {
+                    let _ = reply.send(provider.fetch_units(&media_id));
+                }
+                PluginCmd::FetchMediaDetails { media_id, reply } => {
+                    let _ = reply.send(provider.fetch_media_details(&media_id));
+                }
+                PluginCmd::FetchMediaPage { kind, query, page, reply } => {
+                    let _ = reply.send(provider.fetch_media_page(kind, &query, &page));
+                }
+                PluginCmd::FetchUnitsPage { media_id, page, reply } => {
+                    let _ = reply.send(provider.fetch_units_page(&media_id, &page));
+                }
+                PluginCmd::Search { request, page, reply } => {
+                    let res = check_request(provider.name(), provider.search_capabilities().as_ref(), &request)
+                        .and_then(|_| provider.search(&request, &page));
+                    let _ = reply.send(res);
+                }
+                PluginCmd::GetSearchCapabilities { reply } => {
+                    let _ = reply.send(Ok(provider.search_capabilities()));
+                }
+                PluginCmd::ListFeeds { reply } => {
+                    let _ = reply.send(provider.list_feeds());
+                }
+                PluginCmd::FetchFeed { feed_id, cursor, reply } => {
+                    let _ = reply.send(provider.fetch_feed(&feed_id, cursor));
+                }
+                PluginCmd::FetchAssets { unit_id, reply } => {
+                    let _ = reply.send(provider.fetch_assets(&unit_id));
+                }
+                PluginCmd::ReadAsset { url, reply } => {
+                    let _ = reply.send(provider.read_asset(&url));
+                }
+                PluginCmd::TransformsAssets { reply } => {
+                    let _ = reply.send(Ok(provider.transforms_assets()));
+                }
+                PluginCmd::TransformAsset { asset, bytes, reply } => {
+                    let _ = reply.send(provider.transform_asset(&asset, bytes));
+                }
+                PluginCmd::GetCapabilities { reply, .. } => {
+                    let _ = reply.send(provider.capabilities());
+                }
+                PluginCmd::GetAllowedHosts { reply } => {
+                    let _ = reply.send(Ok(provider.allowed_hosts().unwrap_or_default()));
+                }
+                PluginCmd::GetHostPolicy { reply } => {
+                    let _ = reply.send(Ok(HostPolicy::new(provider.allowed_hosts().as_deref())));
+                }
+            }
+        }
+    });
+    PluginWorker { tx, call_timeout: NATIVE_CALL_TIMEOUT, artifact_kind: ArtifactKind::Native, artifact_path }
+}
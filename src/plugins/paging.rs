@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+use crate::plugins::{Media, Unit};
+
+/// How long a full listing fetched to serve pages of an unpaged export is reused
+const MEMO_TTL: Duration = Duration::from_secs(60);
+
+/// Position and size of a requested page, mirroring `page-request` of the `library-v2` world
+#[derive(ComponentType, Lower, Debug, Clone, Default, PartialEq, Eq)]
+#[component(record)]
+pub struct PageRequest {
+    /// Opaque cursor from a previous page's `next_cursor`; takes precedence over offset
+    pub cursor: Option<String>,
+    /// Number of items to skip when no cursor is given
+    pub offset: Option<u32>,
+    /// Maximum number of items to return; the provider picks its own page size when None
+    pub limit: Option<u32>,
+}
+
+impl PageRequest {
+    /// The first page with the given page size
+    pub fn first(limit: u32) -> Self {
+        Self { cursor: None, offset: None, limit: Some(limit) }
+    }
+}
+
+/// One page of a listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page, None on the last page
+    pub next_cursor: Option<String>,
+    /// Total number of items across all pages, if known
+    pub total: Option<u64>,
+}
+
+#[derive(ComponentType, Lift, Debug, Clone)]
+#[component(record)]
+pub(crate) struct MediaPage {
+    items: Vec<Media>,
+    #[component(name = "next-cursor")]
+    next_cursor: Option<String>,
+    total: Option<u64>,
+}
+
+#[derive(ComponentType, Lift, Debug, Clone)]
+#[component(record)]
+pub(crate) struct UnitPage {
+    items: Vec<Unit>,
+    #[component(name = "next-cursor")]
+    next_cursor: Option<String>,
+    total: Option<u64>,
+}
+
+impl From<MediaPage> for Page<Media> {
+    fn from(p: MediaPage) -> Self {
+        Page { items: p.items, next_cursor: p.next_cursor, total: p.total }
+    }
+}
+
+impl From<UnitPage> for Page<Unit> {
+    fn from(p: UnitPage) -> Self {
+        Page { items: p.items, next_cursor: p.next_cursor, total: p.total }
+    }
+}
+
+/// Serve a page out of a complete listing, for providers without paged exports.
+/// Cursors produced here are plain offsets.
+pub(crate) fn paginate<T: Clone>(items: &[T], page: &PageRequest) -> Page<T> {
+    let start = page.cursor.as_deref()
+        .and_then(|c| c.parse::<usize>().ok())
+        .or(page.offset.map(|o| o as usize))
+        .unwrap_or(0)
+        .min(items.len());
+    let end = match page.limit {
+        Some(limit) => start.saturating_add(limit.max(1) as usize).min(items.len()),
+        None => items.len(),
+    };
+    Page {
+        items: items[start..end].to_vec(),
+        next_cursor: (end < items.len()).then(|| end.to_string()),
+        total: Some(items.len() as u64),
+    }
+}
+
+/// The most recent full listing fetched to serve pages, so walking a long listing does not refetch it per page
+pub(crate) struct ListingMemo<T> {
+    entry: Option<(String, Instant, Vec<T>)>,
+}
+
+impl<T> Default for ListingMemo<T> {
+    fn default() -> Self {
+        Self { entry: None }
+    }
+}
+
+impl<T: Clone> ListingMemo<T> {
+    /// The memoized listing for `key`, unless it is stale. A first page always refetches.
+    pub(crate) fn get(&self, key: &str, page: &PageRequest) -> Option<&[T]> {
+        if page.cursor.is_none() && page.offset.unwrap_or(0) == 0 {
+            return None;
+        }
+        match &self.entry {
+            Some((k, at, items)) if k == key && at.elapsed() < MEMO_TTL => Some(items),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn put(&mut self, key: &str, items: Vec<T>) -> &[T] {
+        &self.entry.insert((key.to_string(), Instant::now(), items)).2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(cursor: Option<&str>, offset: Option<u32>, limit: Option<u32>) -> PageRequest {
+        PageRequest { cursor: cursor.map(str::to_string), offset, limit }
+    }
+
+    #[test]
+    fn pages_through_items_with_offset_cursors() {
+        let items: Vec<u32> = (0..5).collect();
+        let first = paginate(&items, &PageRequest::first(2));
+        assert_eq!((first.items, first.next_cursor.as_deref(), first.total), (vec![0, 1], Some("2"), Some(5)));
+        let last = paginate(&items, &request(Some("4"), None, Some(2)));
+        assert_eq!((last.items, last.next_cursor), (vec![4], None));
+    }
+
+    #[test]
+    fn cursor_takes_precedence_over_offset() {
+        let items: Vec<u32> = (0..5).collect();
+        assert_eq!(paginate(&items, &request(Some("3"), Some(1), Some(1))).items, vec![3]);
+        assert_eq!(paginate(&items, &request(Some("junk"), Some(1), Some(1))).items, vec![1]);
+    }
+
+    #[test]
+    fn past_the_end_is_an_empty_last_page() {
+        let items: Vec<u32> = (0..3).collect();
+        for page in [request(None, Some(7), Some(2)), request(Some("99"), None, None), request(Some("3"), None, Some(1))] {
+            let page = paginate(&items, &page);
+            assert!(page.items.is_empty());
+            assert_eq!(page.next_cursor, None);
+            assert_eq!(page.total, Some(3));
+        }
+    }
+
+    #[test]
+    fn zero_limit_returns_one_item_and_no_limit_returns_the_rest() {
+        let items: Vec<u32> = (0..3).collect();
+        assert_eq!(paginate(&items, &request(None, None, Some(0))).items, vec![0]);
+        let rest = paginate(&items, &request(None, Some(1), None));
+        assert_eq!((rest.items, rest.next_cursor), (vec![1, 2], None));
+    }
+
+    #[test]
+    fn memo_is_skipped_for_first_pages_and_other_keys() {
+        let mut memo = ListingMemo::default();
+        memo.put("a", vec![1, 2, 3]);
+        assert!(memo.get("a", &PageRequest::first(2)).is_none());
+        assert_eq!(memo.get("a", &request(Some("2"), None, Some(2))), Some(&[1, 2, 3][..]));
+        assert!(memo.get("b", &request(Some("2"), None, Some(2))).is_none());
+    }
+}
@@ -1,21 +1,35 @@
 use tokio::runtime::Runtime;
 use tracing::debug;
-use url::Url;
-use wasmtime::{component::*, Store};
+use wasmtime::{component::*, Precompiled, Store, StoreLimitsBuilder};
 use std::{sync::Arc as StdArc};
 use std::time::{Duration, Instant};
 use std::sync::{atomic::AtomicU64, Arc};
-use wasmtime_wasi::WasiCtxBuilder;
 
+use crate::plugins::cache::CompileCache;
 use crate::plugins::config::PluginConfig;
+use crate::plugins::error::{PluginError, ProviderError};
 use crate::plugins::host::Host;
+use crate::plugins::hosts::HostPolicy;
+use crate::plugins::paging::{paginate, ListingMemo, MediaPage, Page, PageRequest, UnitPage};
+use crate::plugins::sandbox;
+use crate::plugins::details::MediaDetails;
+use crate::plugins::feeds::Feed;
+use crate::plugins::search::{check_request, SearchCapabilities, SearchRequest};
 use crate::plugins::*;
 
 pub(crate) struct Plugin {
     pub(crate) name: String,
     pub(crate) store: Store<Host>,
-    pub(crate) _bindings: Library,
+    /// Typed bindings, only for plugins built against the original `library` world
+    pub(crate) _bindings: Option<Library>,
+    pub(crate) world: WorldVersion,
     pub(crate) caps: Option<ProviderCapabilities>,
+    /// Structured search support, filled in alongside `caps`
+    pub(crate) search_caps: Option<SearchCapabilities>,
+    /// Whether the plugin advertises feeds, filled in alongside `caps`
+    pub(crate) has_feeds: bool,
+    /// Whether fetched images must go through `transform-asset`, filled in alongside `caps`
+    pub(crate) has_asset_transform: bool,
     pub(crate) rate_limit: Duration,
     pub(crate) slow_warn: Duration,
     pub(crate) call_timeout: Duration,
@@ -23,40 +37,120 @@ pub(crate) struct Plugin {
     pub(crate) epoch_ticks: Arc<AtomicU64>,
     pub(crate) epoch_interval: Duration,
     pub(crate) allowed_hosts: Option<Vec<String>>,
+    pub(crate) host_policy: HostPolicy,
     pub(crate) _instance: wasmtime::component::Instance,
     pub(crate) _component: Component,
+    pub(crate) artifact_kind: ArtifactKind,
+    /// Full listings backing pages served to callers when the plugin has no paged exports
+    media_memo: ListingMemo<Media>,
+    units_memo: ListingMemo<Unit>,
     rt: StdArc<Runtime>,
 }
 
+/// Which revision of the plugin world a component implements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WorldVersion {
+    /// `library`: fetch functions return bare lists
+    V1,
+    /// `library-v2`: fetch functions return `result<list<T>, provider-error>`
+    V2,
+}
+
+/// `provider-capabilities` as extended by the `library-v2` world
+#[derive(ComponentType, Lift, Debug, Clone)]
+#[component(record)]
+struct CapabilitiesV2 {
+    #[component(name = "media-types")]
+    media_types: Vec<MediaType>,
+    #[component(name = "unit-kinds")]
+    unit_kinds: Vec<UnitKind>,
+    #[component(name = "asset-kinds")]
+    asset_kinds: Vec<AssetKind>,
+    search: Option<SearchCapabilities>,
+    feeds: bool,
+    #[component(name = "asset-transform")]
+    asset_transform: bool,
+}
+
+/// Look up an export by its plain name or its `library#` prefixed form
+fn find_export(store: &mut Store<Host>, instance: &Instance, export: &str) -> Result<Func> {
+    instance.get_func(&mut *store, export)
+        .or_else(|| instance.get_func(&mut *store, format!("library#{}", export)))
+        .ok_or_else(|| anyhow!("missing export {} (tried '{}' and 'library#{}')", export, export, export))
+}
+
+/// Tell the worlds apart by the signature of `fetchmedialist`
+fn detect_world(store: &mut Store<Host>, instance: &Instance) -> WorldVersion {
+    match find_export(store, instance, "fetchmedialist") {
+        Ok(func) if func.typed::<(MediaType, String), (Result<Vec<Media>, ProviderError>,)>(&*store).is_ok() => WorldVersion::V2,
+        _ => WorldVersion::V1,
+    }
+}
+
+/// Load a component from either a precompiled `.cwasm` or a `.wasm` artifact.
+/// Precompiled artifacts are validated against the running engine's configuration (wasmtime version,
+/// target and compilation settings) by `Component::deserialize_file`, which errors on any mismatch so
+/// the caller can fall back to the `.wasm`.
+/// Plain `.wasm` artifacts go through the compile cache when one is configured.
+fn load_component(
+    engine: &Engine,
+    plugin_path: &PathBuf,
+    cache: Option<&CompileCache>,
+) -> Result<(Component, ArtifactKind)> {
+    // The extension decides how the artifact is read, so a damaged `.cwasm` is reported rather than
+    // handed to the `.wasm` compiler
+    let precompiled = if plugin_path.extension().is_some_and(|e| e == "cwasm") {
+        let kind = Engine::detect_precompiled_file(plugin_path)
+            .with_context(|| format!("failed to read precompiled artifact {}", plugin_path.display()))?;
+        Some(kind.ok_or_else(|| anyhow!("{} is not a precompiled wasmtime artifact", plugin_path.display()))?)
+    } else {
+        None
+    };
+    match precompiled {
+        Some(Precompiled::Component) => {
+            // SAFETY: the artifact must have been produced by `Engine::precompile_component`; the file
+            // header and engine compatibility are verified by wasmtime before any code is mapped.
+            let component = unsafe { Component::deserialize_file(engine, plugin_path) }
+                .map_err(|e| anyhow!("incompatible precompiled artifact {}: {}", plugin_path.display(), e))?;
+            Ok((component, ArtifactKind::Precompiled))
+        }
+        Some(Precompiled::Module) => Err(anyhow!(
+            "precompiled artifact {} is a core module, expected a component",
+            plugin_path.display()
+        )),
+        None => {
+            #[cfg(target_os = "ios")]
+            {
+                let _ = cache;
+                Err(anyhow!(
+                    "Pulley host requires precompiled .cwasm artifact; found {}",
+                    plugin_path.display()
+                ))
+            }
+            #[cfg(not(target_os = "ios"))]
+            {
+                match cache {
+                    Some(cache) => {
+                        let (component, hit) = cache.load_or_compile(engine, plugin_path)?;
+                        Ok((component, if hit { ArtifactKind::Cached } else { ArtifactKind::Wasm }))
+                    }
+                    None => Ok((Component::from_file(engine, plugin_path)?, ArtifactKind::Wasm)),
+                }
+            }
+        }
+    }
+}
+
 impl Plugin {
     pub async fn new_async(
         engine: &Engine,
         plugin_path: &PathBuf,
         epoch_ticks: Arc<AtomicU64>,
         epoch_interval: Duration,
+        cache: Option<Arc<CompileCache>>,
         rt: StdArc<Runtime>,
     ) -> Result<Self> {
-        // let component = if plugin_path
-        //     .extension()
-        //     .and_then(|ext| ext.to_str())
-        //     .map(|ext| ext.eq_ignore_ascii_case("cwasm"))
-        //     .unwrap_or(false)
-        // {
-        //     unsafe { Component::deserialize_file(engine, plugin_path)? }
-        // } else {
-        //     #[cfg(target_os = "ios")]
-        //     {
-        //         return Err(anyhow!(
-        //             "Pulley host requires precompiled .cwasm artifact; found {}",
-        //             plugin_path.display()
-        //         ));
-        //     }
-        //     #[cfg(not(target_os = "ios"))]
-        //     {
-        //         Component::from_file(engine, plugin_path)?
-        //     }
-        // };
-        let component = Component::from_file(engine, plugin_path)?;
+        let (component, artifact_kind) = load_component(engine, plugin_path, cache.as_deref())?;
 
         let cfg_path = plugin_path.with_extension("toml");
         let cfg: PluginConfig = std::fs::read_to_string(&cfg_path)
@@ -69,19 +163,32 @@ impl Plugin {
                 .filter(|h| !h.is_empty())
                 .collect()
         });
-        let mut builder = WasiCtxBuilder::new();
-        builder.inherit_stdout().inherit_stderr().inherit_env();
-        if let Some(list) = &allowed_hosts {
-            builder.env("AWASM_ALLOWED_HOSTS", list.join(","));
-        }
-        let wasi = builder.build();
+        let name = plugin_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let host_policy = HostPolicy::new(allowed_hosts.as_deref());
+        let wasi = sandbox::build_wasi(&name, allowed_hosts.as_deref(), cfg.env.as_ref());
         let http = wasmtime_wasi_http::WasiHttpCtx::new();
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(cfg.max_memory_mb.unwrap_or(256).saturating_mul(1024 * 1024) as usize)
+            .tables(cfg.max_tables.unwrap_or(64))
+            .instances(cfg.max_instances.unwrap_or(64))
+            .table_elements(cfg.max_table_elements.unwrap_or(100_000))
+            .trap_on_grow_failure(true)
+            .build();
         let host = Host {
+            name: name.clone(),
             wasi,
             table: wasmtime_wasi::ResourceTable::new(),
             http,
+            hosts: host_policy.clone(),
+            limits,
+            limit_exceeded: None,
         };
         let mut store = Store::new(engine, host);
+        store.limiter(|host| host);
         let now = epoch_ticks.load(Ordering::Relaxed);
         let far = now.saturating_add(1_000_000_000);
         store.set_epoch_deadline(far);
@@ -90,17 +197,22 @@ impl Plugin {
         wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)?;
         // (If sockets support was required explicitly it would be added here; current API couples http to sockets internally when NetworkCtx present.)
         let instance = linker.instantiate_async(&mut store, &component).await?;
-        let bindings = Library::new(&mut store, &instance)?;
+        let world = detect_world(&mut store, &instance);
+        let bindings = match world {
+            WorldVersion::V1 => Some(Library::new(&mut store, &instance)?),
+            WorldVersion::V2 => None,
+        };
+        debug!(plugin=%name, ?world, "instantiated plugin");
         let caps = None; // defer to plugin load time
         Ok(Self {
-            name: plugin_path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown")
-                .to_string(),
+            name,
             store,
             _bindings: bindings,
+            world,
             caps,
+            search_caps: None,
+            has_feeds: false,
+            has_asset_transform: false,
             rate_limit: Duration::from_millis(cfg.rate_limit_ms.unwrap_or(150)),
             slow_warn: Duration::from_secs(5),
             call_timeout: Duration::from_millis(cfg.call_timeout_ms.unwrap_or(15_000)),
@@ -108,8 +220,12 @@ impl Plugin {
             epoch_ticks,
             epoch_interval,
             allowed_hosts,
+            host_policy,
             _instance: instance,
             _component: component,
+            artifact_kind,
+            media_memo: ListingMemo::default(),
+            units_memo: ListingMemo::default(),
             rt,
         })
     }
@@ -124,21 +240,11 @@ impl Plugin {
         self.throttle();
         self.set_deadline();
         let start = Instant::now();
-        let res = self.retry_once(|this| {
-            // Try plain export name first, then prefixed variant
-            let func = this._instance.get_func(&mut this.store, "fetchmedialist")
-                .or_else(|| this._instance.get_func(&mut this.store, "library#fetchmedialist"))
-                .ok_or_else(|| anyhow!("missing export fetchmedialist (tried 'fetchmedialist' and 'library#fetchmedialist')"))?;
-            let typed = func.typed::<(MediaType, String), (Vec<Media>,)>(&this.store)?;
-            let (result_vec,) = this.rt.block_on(typed.call_async(&mut this.store, (kind.clone(), query.to_string())))
-                .map_err(|e| anyhow!("Failed to call fetchmedialist async: {}", e))?;
-            this.rt.block_on(typed.post_return_async(&mut this.store))?;
-            Ok(result_vec)
-        }, "fetchmedialist");
+        let res: Result<Vec<Media>> = self.call_list("fetchmedialist", (kind, query.to_string()));
         self.clear_deadline();
         self.warn_if_slow(start, "fetchmedialist");
         let mut list = match res {
-            Ok(v) => {
+            Ok(v) if self.world == WorldVersion::V1 => {
                 // Inspect and log sentinel error entries before filtering them out
                 let mut filtered: Vec<Media> = Vec::with_capacity(v.len());
                 let mut suppressed = 0usize;
@@ -154,24 +260,11 @@ impl Plugin {
                 }
                 filtered
             }
-            Err(e) => {
-                error!(plugin=%self.name, error=%e, "fetchmedialist failed");
-                Vec::new()
-            }
+            Ok(v) => v,
+            Err(e) => self.list_failure(e, "fetchmedialist")?,
         };
         debug!(plugin=%self.name, query, count=list.len(), "fetch_media_list done");
-        for m in &mut list {
-            if let Some(u) = &m.url {
-                if !self.url_allowed(u) {
-                    m.url = None;
-                }
-            }
-            if let Some(c) = &m.cover_url {
-                if !self.url_allowed(c) {
-                    m.cover_url = None;
-                }
-            }
-        }
+        self.strip_disallowed_media_urls(&mut list);
         Ok(list)
     }
 
@@ -183,32 +276,14 @@ impl Plugin {
         self.throttle();
         self.set_deadline();
         let start = Instant::now();
-        let res = self.retry_once(|this| {
-            let func = this._instance.get_func(&mut this.store, "fetchunits")
-                .or_else(|| this._instance.get_func(&mut this.store, "library#fetchunits"))
-                .ok_or_else(|| anyhow!("missing export fetchunits (tried 'fetchunits' and 'library#fetchunits')"))?;
-            let typed = func.typed::<(String,), (Vec<Unit>,)>(&this.store)?;
-            let (result_vec,) = this.rt.block_on(typed.call_async(&mut this.store, (media_id.to_string(),)))
-                .map_err(|e| anyhow!("Failed to call fetchunits async: {}", e))?;
-            this.rt.block_on(typed.post_return_async(&mut this.store))?;
-            Ok(result_vec)
-        }, "fetchunits");
+        let res: Result<Vec<Unit>> = self.call_list("fetchunits", (media_id.to_string(),));
         self.clear_deadline();
         self.warn_if_slow(start, "fetchunits");
         let mut units = match res {
             Ok(v) => v,
-            Err(e) => {
-                error!(plugin=%self.name, error=%e, "fetchunits failed");
-                Vec::new()
-            }
+            Err(e) => self.list_failure(e, "fetchunits")?,
         };
-        for u in &mut units {
-            if let Some(uurl) = &u.url {
-                if !self.url_allowed(uurl) {
-                    u.url = None;
-                }
-            }
-        }
+        self.strip_disallowed_unit_urls(&mut units);
         Ok(units)
     }
 
@@ -220,32 +295,184 @@ impl Plugin {
         self.throttle();
         self.set_deadline();
         let start = Instant::now();
-        let res = self.retry_once(|this| {
-            let func = this._instance.get_func(&mut this.store, "fetchassets")
-                .or_else(|| this._instance.get_func(&mut this.store, "library#fetchassets"))
-                .ok_or_else(|| anyhow!("missing export fetchassets (tried 'fetchassets' and 'library#fetchassets')"))?;
-            let typed = func.typed::<(String,), (Vec<Asset>,)>(&this.store)?;
-            let (result_vec,) = this.rt.block_on(typed.call_async(&mut this.store, (unit_id.to_string(),)))
-                .map_err(|e| anyhow!("Failed to call fetchassets async: {}", e))?;
-            this.rt.block_on(typed.post_return_async(&mut this.store))?;
-            Ok(result_vec)
-        }, "fetchassets");
+        let res: Result<Vec<Asset>> = match self.world {
+            WorldVersion::V1 => self.call_list::<_, bindings::Asset>("fetchassets", (unit_id.to_string(),))
+                .map(|list| list.into_iter().map(Asset::from).collect()),
+            WorldVersion::V2 => self.call_list("fetchassets", (unit_id.to_string(),)),
+        };
         self.clear_deadline();
         self.warn_if_slow(start, "fetchassets");
         let assets = match res {
             Ok(v) => v,
-            Err(e) => {
-                error!(plugin=%self.name, error=%e, "fetchassets failed");
-                Vec::new()
-            }
+            Err(e) => self.list_failure(e, "fetchassets")?,
         };
-        let filtered: Vec<Asset> = assets
+        let mut filtered: Vec<Asset> = assets
             .into_iter()
             .filter(|a| self.url_allowed(&a.url))
             .collect();
+        self.strip_denied_headers(&mut filtered);
         Ok(filtered)
     }
 
+    /// Whether fetched images of this plugin must be passed through `transform_asset`
+    pub(crate) fn transforms_assets(&mut self) -> Result<bool> {
+        self.get_capabilities()?;
+        Ok(self.has_asset_transform)
+    }
+
+    /// Runs the plugin's `transform-asset` export over fetched asset bytes. Not rate limited, since it
+    /// does no network I/O.
+    pub(crate) fn transform_asset(&mut self, asset: &Asset, bytes: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.transforms_assets()? {
+            return Ok(bytes);
+        }
+        self.set_deadline();
+        let start = Instant::now();
+        let len = bytes.len();
+        let res: Result<Vec<u8>> = self.call_fallible("transform-asset", (asset.clone(), bytes));
+        self.clear_deadline();
+        self.warn_if_slow(start, "transform-asset");
+        debug!(plugin=%self.name, url=%asset.url, len, "transform_asset done");
+        res
+    }
+
+    /// Fetches extended metadata for a media item from plugins with a `fetchmediadetails` export
+    pub(crate) fn fetch_media_details(&mut self, media_id: &str) -> Result<MediaDetails> {
+        if self.world == WorldVersion::V1 || !self.has_export("fetchmediadetails") {
+            return Err(PluginError::InvalidRequest { plugin: self.name.clone(), reason: "media details are not supported".to_string() }.into());
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<MediaDetails> = self.call_fallible("fetchmediadetails", (media_id.to_string(),));
+        self.clear_deadline();
+        self.warn_if_slow(start, "fetchmediadetails");
+        let mut details = res?;
+        self.strip_disallowed_media_urls(std::slice::from_mut(&mut details.media));
+        if details.banner_url.as_deref().is_some_and(|u| !self.url_allowed(u)) {
+            details.banner_url = None;
+        }
+        Ok(details)
+    }
+
+    /// Fetches one page of media items. Plugins without `fetchmedialist-page` are served from their full listing.
+    pub(crate) fn fetch_media_page(&mut self, kind: MediaType, query: &str, page: &PageRequest) -> Result<Page<Media>> {
+        if self.world == WorldVersion::V1 || !self.has_export("fetchmedialist-page") {
+            let key = format!("{}\u{1f}{}", serde_json::to_string(&kind)?, query);
+            if let Some(items) = self.media_memo.get(&key, page) {
+                return Ok(paginate(items, page));
+            }
+            let items = self.fetch_media_list(kind, query)?;
+            return Ok(paginate(self.media_memo.put(&key, items), page));
+        }
+        if matches!(&self.allowed_hosts, Some(v) if v.is_empty()) {
+            return Ok(paginate(&[], page));
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<MediaPage> = self.call_fallible("fetchmedialist-page", (kind, query.to_string(), page.clone()));
+        self.clear_deadline();
+        self.warn_if_slow(start, "fetchmedialist-page");
+        let mut page: Page<Media> = res?.into();
+        debug!(plugin=%self.name, query, count=page.items.len(), "fetch_media_page done");
+        self.strip_disallowed_media_urls(&mut page.items);
+        Ok(page)
+    }
+
+    /// Fetches one page of units. Plugins without `fetchunits-page` are served from their full listing.
+    pub(crate) fn fetch_units_page(&mut self, media_id: &str, page: &PageRequest) -> Result<Page<Unit>> {
+        if self.world == WorldVersion::V1 || !self.has_export("fetchunits-page") {
+            if let Some(items) = self.units_memo.get(media_id, page) {
+                return Ok(paginate(items, page));
+            }
+            let items = self.fetch_units(media_id)?;
+            return Ok(paginate(self.units_memo.put(media_id, items), page));
+        }
+        if matches!(&self.allowed_hosts, Some(v) if v.is_empty()) {
+            return Ok(paginate(&[], page));
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<UnitPage> = self.call_fallible("fetchunits-page", (media_id.to_string(), page.clone()));
+        self.clear_deadline();
+        self.warn_if_slow(start, "fetchunits-page");
+        let mut page: Page<Unit> = res?.into();
+        self.strip_disallowed_unit_urls(&mut page.items);
+        Ok(page)
+    }
+
+    /// Runs a structured search after validating it against the advertised search capabilities.
+    /// Plain requests to plugins without a `search` export are answered by `fetch_media_page`; filtered ones
+    /// are rejected there even if capabilities were advertised, since the filters would be dropped.
+    pub(crate) fn search(&mut self, request: &SearchRequest, page: &PageRequest) -> Result<Page<Media>> {
+        let caps = self.get_search_capabilities()?;
+        check_request(&self.name, caps.as_ref(), request)?;
+        if self.world == WorldVersion::V1 || !self.has_export("search") {
+            if !request.is_plain() {
+                let reason = "structured search is advertised but the search export is missing".to_string();
+                return Err(PluginError::InvalidRequest { plugin: self.name.clone(), reason }.into());
+            }
+            return self.fetch_media_page(request.kind.clone(), &request.query, page);
+        }
+        if matches!(&self.allowed_hosts, Some(v) if v.is_empty()) {
+            return Ok(paginate(&[], page));
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<MediaPage> = self.call_fallible("search", (request.clone(), page.clone()));
+        self.clear_deadline();
+        self.warn_if_slow(start, "search");
+        let mut page: Page<Media> = res?.into();
+        debug!(plugin=%self.name, query=%request.query, count=page.items.len(), "search done");
+        self.strip_disallowed_media_urls(&mut page.items);
+        Ok(page)
+    }
+
+    /// Search capabilities, fetching the provider capabilities first if they are not cached
+    pub(crate) fn get_search_capabilities(&mut self) -> Result<Option<SearchCapabilities>> {
+        self.get_capabilities()?;
+        Ok(self.search_caps.clone())
+    }
+
+    /// Lists the plugin's feeds; empty unless it advertises them
+    pub(crate) fn list_feeds(&mut self) -> Result<Vec<Feed>> {
+        self.get_capabilities()?;
+        if !self.has_feeds {
+            return Ok(Vec::new());
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<Vec<Feed>> = self.call_fallible("list-feeds", ());
+        self.clear_deadline();
+        self.warn_if_slow(start, "list-feeds");
+        res
+    }
+
+    /// Fetches one page of a feed, starting at the top when cursor is None
+    pub(crate) fn fetch_feed(&mut self, feed_id: &str, cursor: Option<String>) -> Result<Page<Media>> {
+        self.get_capabilities()?;
+        if !self.has_feeds {
+            return Err(PluginError::InvalidRequest { plugin: self.name.clone(), reason: "feeds are not supported".to_string() }.into());
+        }
+        if matches!(&self.allowed_hosts, Some(v) if v.is_empty()) {
+            return Ok(paginate(&[], &PageRequest::default()));
+        }
+        self.throttle();
+        self.set_deadline();
+        let start = Instant::now();
+        let res: Result<MediaPage> = self.call_fallible("fetch-feed", (feed_id.to_string(), cursor));
+        self.clear_deadline();
+        self.warn_if_slow(start, "fetch-feed");
+        let mut page: Page<Media> = res?.into();
+        debug!(plugin=%self.name, feed_id, count=page.items.len(), "fetch_feed done");
+        self.strip_disallowed_media_urls(&mut page.items);
+        Ok(page)
+    }
+
     /// Returns the cached capabilities if available, otherwise fetches them from the plugin.
     pub(crate) fn get_capabilities(&mut self) -> Result<ProviderCapabilities> {
         if let Some(c) = &self.caps {
@@ -256,60 +483,126 @@ impl Plugin {
 
     /// ----------------------- Helpers -----------------------
 
-    fn get_capabilities_refresh(&mut self) -> Result<ProviderCapabilities> {
+    pub(crate) fn get_capabilities_refresh(&mut self) -> Result<ProviderCapabilities> {
         self.throttle();
         self.set_deadline();
         let start = Instant::now();
-        let res = self.retry_once(|this| {
-            let func = this._instance.get_func(&mut this.store, "getcapabilities")
-                .or_else(|| this._instance.get_func(&mut this.store, "library#getcapabilities"))
-                .ok_or_else(|| anyhow!("missing export getcapabilities (tried 'getcapabilities' and 'library#getcapabilities')"))?;
-            let typed = func.typed::<(), (ProviderCapabilities,)>(&this.store)?;
-            let (caps,) = this.rt.block_on(typed.call_async(&mut this.store, ()))
-                .map_err(|e| anyhow!("Failed to call getcapabilities async: {}", e))?;
-            this.rt.block_on(typed.post_return_async(&mut this.store))?;
-            Ok(caps)
-        }, "getcapabilities");
+        let res = match self.world {
+            WorldVersion::V1 => self.call_export::<(), ProviderCapabilities>("getcapabilities", ()).map(|c| (c, None, false, false)),
+            WorldVersion::V2 => self.call_export::<(), CapabilitiesV2>("getcapabilities", ()).map(|c| {
+                let caps = ProviderCapabilities { media_types: c.media_types, unit_kinds: c.unit_kinds, asset_kinds: c.asset_kinds };
+                (caps, c.search, c.feeds, c.asset_transform)
+            }),
+        };
         self.clear_deadline();
         self.warn_if_slow(start, "getcapabilities");
-        if let Ok(c) = &res {
-            self.caps = Some(c.clone());
+        let (caps, search_caps, has_feeds, has_asset_transform) = res?;
+        self.caps = Some(caps.clone());
+        self.search_caps = search_caps;
+        self.has_feeds = has_feeds;
+        self.has_asset_transform = has_asset_transform;
+        Ok(caps)
+    }
+
+    /// Call a list-returning export in whichever world the plugin implements
+    fn call_list<P, T>(&mut self, export: &'static str, params: P) -> Result<Vec<T>>
+    where
+        P: ComponentNamedList + Lower + Clone + Send + Sync,
+        T: ComponentType + Lift + Send + Sync + 'static,
+    {
+        match self.world {
+            WorldVersion::V1 => self.call_export(export, params),
+            WorldVersion::V2 => self.call_fallible(export, params),
         }
-        res
     }
 
-    /// ----------------------- Public Helpers -----------------------
-    
-    pub(crate) fn url_allowed(&self, url: &str) -> bool {
-        match &self.allowed_hosts {
-            None => true,
-            Some(list) => {
-                if list.is_empty() {
-                    return false;
+    /// Call an export returning `result<R, provider-error>`. Errors reported by the provider come back as
+    /// `PluginError::Provider` and are not retried.
+    fn call_fallible<P, R>(&mut self, export: &'static str, params: P) -> Result<R>
+    where
+        P: ComponentNamedList + Lower + Clone + Send + Sync,
+        R: ComponentType + Lift + Send + Sync + 'static,
+    {
+        let res: std::result::Result<R, ProviderError> = self.call_export(export, params)?;
+        res.map_err(|error| {
+            debug!(plugin=%self.name, op=export, %error, "provider reported an error");
+            PluginError::Provider { plugin: self.name.clone(), error }.into()
+        })
+    }
+
+    /// Call an export with a single result, retrying once on failure
+    fn call_export<P, R>(&mut self, export: &'static str, params: P) -> Result<R>
+    where
+        P: ComponentNamedList + Lower + Clone + Send + Sync,
+        R: ComponentType + Lift + Send + Sync + 'static,
+    {
+        self.retry_once(|this| {
+            let func = find_export(&mut this.store, &this._instance, export)?;
+            let typed = func.typed::<P, (R,)>(&this.store)?;
+            let (result,) = this.rt.block_on(typed.call_async(&mut this.store, params.clone()))
+                .map_err(|e| anyhow!("Failed to call {} async: {}", export, e))?;
+            this.rt.block_on(typed.post_return_async(&mut this.store))?;
+            Ok(result)
+        }, export)
+    }
+
+    fn has_export(&mut self, export: &str) -> bool {
+        find_export(&mut self.store, &self._instance, export).is_ok()
+    }
+
+    /// Decide what a failed list call returns: typed errors, and every error from a `library-v2` plugin,
+    /// reach the caller; other failures of original-world plugins are logged and read as an empty list.
+    fn list_failure<T>(&self, e: anyhow::Error, op: &str) -> Result<Vec<T>> {
+        if self.world == WorldVersion::V2 || e.downcast_ref::<PluginError>().is_some() {
+            return Err(e);
+        }
+        error!(plugin=%self.name, error=%e, "{} failed", op);
+        Ok(Vec::new())
+    }
+
+    fn strip_disallowed_media_urls(&self, list: &mut [Media]) {
+        for m in list {
+            if let Some(u) = &m.url {
+                if !self.url_allowed(u) {
+                    m.url = None;
+                }
+            }
+            if let Some(c) = &m.cover_url {
+                if !self.url_allowed(c) {
+                    m.cover_url = None;
                 }
-                let Ok(parsed) = Url::parse(url) else {
-                    return false;
-                };
-                match parsed.scheme() {
-                    "http" | "https" => {}
-                    _ => return false,
+            }
+        }
+    }
+
+    fn strip_denied_headers(&self, assets: &mut [Asset]) {
+        for a in assets {
+            a.headers.retain(|h| {
+                let allowed = h.is_allowed();
+                if !allowed {
+                    warn!(plugin=%self.name, header=%h.name, url=%a.url, "dropping denied asset header");
+                }
+                allowed
+            });
+        }
+    }
+
+    fn strip_disallowed_unit_urls(&self, units: &mut [Unit]) {
+        for u in units {
+            if let Some(uurl) = &u.url {
+                if !self.url_allowed(uurl) {
+                    u.url = None;
                 }
-                let Some(host) = parsed.host_str() else {
-                    return false;
-                };
-                let host = host.to_ascii_lowercase();
-                list.iter().any(|allowed| {
-                    let a = allowed.as_str();
-                    if let Some(stripped) = a.strip_prefix("*.") {
-                        host == stripped || host.ends_with(&format!(".{}", stripped))
-                    } else {
-                        host == a
-                    }
-                })
             }
         }
     }
 
+    /// ----------------------- Public Helpers -----------------------
+    
+    pub(crate) fn url_allowed(&self, url: &str) -> bool {
+        self.host_policy.allows_url(url)
+    }
+
     pub(crate) fn set_deadline(&mut self) {
         let now = self.epoch_ticks.load(Ordering::Relaxed);
         let per_tick_ms = self.epoch_interval.as_millis().max(1) as u128;
@@ -349,6 +642,10 @@ impl Plugin {
             self.set_deadline();
             let res = f(self);
             self.clear_deadline();
+            if let Some(detail) = self.store.data_mut().limit_exceeded.take() {
+                error!(plugin=%self.name, op, detail, "plugin exceeded resource limits");
+                return Err(PluginError::ResourceLimitExceeded { plugin: self.name.clone(), detail }.into());
+            }
             match res {
                 Ok(v) => return Ok(v),
                 Err(e1) if attempt == 0 => {
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use tracing::{info, warn};
+use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+
+/// Environment variables reserved for the host; plugin configs cannot override them
+const RESERVED_ENV: &[&str] = &["AWASM_ALLOWED_HOSTS"];
+
+/// Longest partial line buffered before it is emitted without a trailing newline
+const MAX_LINE: usize = 4096;
+
+/// Build the WASI context for a plugin: no inherited stdio, args, env or preopened directories.
+/// The guest only sees `AWASM_ALLOWED_HOSTS` and the `[env]` entries declared in its own config.
+pub(crate) fn build_wasi(
+    plugin: &str,
+    allowed_hosts: Option<&[String]>,
+    declared_env: Option<&HashMap<String, String>>,
+) -> WasiCtx {
+    let mut builder = WasiCtxBuilder::new();
+    builder
+        .stdout(PluginOutput::new(plugin, OutputKind::Stdout))
+        .stderr(PluginOutput::new(plugin, OutputKind::Stderr));
+    if let Some(list) = allowed_hosts {
+        builder.env("AWASM_ALLOWED_HOSTS", list.join(","));
+    }
+    if let Some(env) = declared_env {
+        let mut keys: Vec<&String> = env.keys().collect();
+        keys.sort();
+        for key in keys {
+            if RESERVED_ENV.contains(&key.as_str()) {
+                warn!(plugin, key=%key, "ignoring reserved env key in plugin config");
+                continue;
+            }
+            builder.env(key, &env[key]);
+        }
+    }
+    builder.build()
+}
+
+#[derive(Clone, Copy)]
+enum OutputKind {
+    Stdout,
+    Stderr,
+}
+
+/// Plugin stdout/stderr sink that forwards each line as a `tracing` event tagged with the plugin name
+#[derive(Clone)]
+struct PluginOutput {
+    plugin: String,
+    kind: OutputKind,
+}
+
+impl PluginOutput {
+    fn new(plugin: &str, kind: OutputKind) -> Self {
+        Self { plugin: plugin.to_string(), kind }
+    }
+}
+
+impl IsTerminal for PluginOutput {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl StdoutStream for PluginOutput {
+    fn async_stream(&self) -> Box<dyn AsyncWrite + Send + Sync> {
+        Box::new(LineLogger { out: self.clone(), buf: Vec::new() })
+    }
+}
+
+/// Buffers guest output until a full line is available, then logs it
+struct LineLogger {
+    out: PluginOutput,
+    buf: Vec<u8>,
+}
+
+impl LineLogger {
+    fn emit(&self, line: &str) {
+        match self.out.kind {
+            OutputKind::Stdout => info!(target: "awasmlib::plugin_output", plugin=%self.out.plugin, stream="stdout", "{}", line),
+            OutputKind::Stderr => warn!(target: "awasmlib::plugin_output", plugin=%self.out.plugin, stream="stderr", "{}", line),
+        }
+    }
+
+    fn drain_lines(&mut self) {
+        for line in take_lines(&mut self.buf) {
+            self.emit(&line);
+        }
+    }
+
+    fn flush_partial(&mut self) {
+        if !self.buf.is_empty() {
+            let line = std::mem::take(&mut self.buf);
+            self.emit(&line_text(&line));
+        }
+    }
+}
+
+/// Remove every complete line from `buf`, plus the remainder once it reaches `MAX_LINE`
+fn take_lines(buf: &mut Vec<u8>) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(pos) = buf.iter().position(|b| *b == b'\n') {
+        let line: Vec<u8> = buf.drain(..=pos).collect();
+        lines.push(line_text(&line[..line.len() - 1]));
+    }
+    if buf.len() >= MAX_LINE {
+        lines.push(line_text(&std::mem::take(buf)));
+    }
+    lines
+}
+
+fn line_text(line: &[u8]) -> String {
+    String::from_utf8_lossy(line).trim_end_matches('\r').to_string()
+}
+
+impl AsyncWrite for LineLogger {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, data: &[u8]) -> Poll<std::io::Result<usize>> {
+        self.buf.extend_from_slice(data);
+        self.drain_lines();
+        Poll::Ready(Ok(data.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.flush_partial();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for LineLogger {
+    fn drop(&mut self) {
+        self.flush_partial();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_writes_are_joined_across_calls() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"hel");
+        assert!(take_lines(&mut buf).is_empty());
+        buf.extend_from_slice(b"lo\nwor");
+        assert_eq!(take_lines(&mut buf), vec!["hello"]);
+        buf.extend_from_slice(b"ld\n\n");
+        assert_eq!(take_lines(&mut buf), vec!["world", ""]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn crlf_endings_are_trimmed() {
+        let mut buf = b"one\r\ntwo\r\nthr".to_vec();
+        assert_eq!(take_lines(&mut buf), vec!["one", "two"]);
+        assert_eq!(buf, b"thr");
+    }
+
+    #[test]
+    fn oversized_line_is_flushed_at_max_line() {
+        let mut buf = vec![b'x'; MAX_LINE - 1];
+        assert!(take_lines(&mut buf).is_empty());
+        buf.push(b'x');
+        let lines = take_lines(&mut buf);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].len(), MAX_LINE);
+        assert!(buf.is_empty());
+    }
+}
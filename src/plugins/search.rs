@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use wasmtime::component::{ComponentType, Lift, Lower};
+
+use crate::plugins::{MediaType, PluginError};
+
+#[derive(ComponentType, Lift, Lower, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[component(enum)]
+#[repr(u8)]
+pub enum PublicationStatus {
+    #[component(name = "ongoing")]
+    Ongoing,
+    #[component(name = "completed")]
+    Completed,
+    #[component(name = "hiatus")]
+    Hiatus,
+    #[component(name = "cancelled")]
+    Cancelled,
+}
+
+#[derive(ComponentType, Lift, Lower, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[component(enum)]
+#[repr(u8)]
+pub enum ContentRating {
+    #[component(name = "safe")]
+    Safe,
+    #[component(name = "suggestive")]
+    Suggestive,
+    #[component(name = "mature")]
+    Mature,
+    #[component(name = "explicit")]
+    Explicit,
+}
+
+#[derive(ComponentType, Lift, Lower, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[component(enum)]
+#[repr(u8)]
+pub enum SortOrder {
+    #[component(name = "relevance")]
+    Relevance,
+    #[component(name = "title")]
+    Title,
+    #[component(name = "latest-update")]
+    LatestUpdate,
+    #[component(name = "newest")]
+    Newest,
+    #[component(name = "popularity")]
+    Popularity,
+    #[component(name = "rating")]
+    Rating,
+}
+
+/// Inclusive range of publication years; an absent bound is open
+#[derive(ComponentType, Lift, Lower, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct YearRange {
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+/// A structured search, mirroring `search-request` of the `library-v2` world.
+/// Empty lists and None mean "no filter".
+#[derive(ComponentType, Lower, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[component(record)]
+pub struct SearchRequest {
+    pub kind: MediaType,
+    pub query: String,
+    /// Genres/tags every result must have
+    #[component(name = "include-tags")]
+    pub include_tags: Vec<String>,
+    /// Genres/tags no result may have
+    #[component(name = "exclude-tags")]
+    pub exclude_tags: Vec<String>,
+    pub status: Option<PublicationStatus>,
+    /// ISO language codes, any of which may match
+    pub languages: Vec<String>,
+    pub year: Option<YearRange>,
+    /// Content ratings, any of which may match
+    #[component(name = "content-ratings")]
+    pub content_ratings: Vec<ContentRating>,
+    pub sort: Option<SortOrder>,
+}
+
+impl SearchRequest {
+    /// A free-text search without filters
+    pub fn new(kind: MediaType, query: &str) -> Self {
+        Self {
+            kind,
+            query: query.to_string(),
+            include_tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            status: None,
+            languages: Vec::new(),
+            year: None,
+            content_ratings: Vec::new(),
+            sort: None,
+        }
+    }
+
+    /// Whether the request carries nothing beyond kind and query, so `fetchmedialist` can answer it
+    pub fn is_plain(&self) -> bool {
+        self.include_tags.is_empty()
+            && self.exclude_tags.is_empty()
+            && self.status.is_none()
+            && self.languages.is_empty()
+            && self.year.is_none()
+            && self.content_ratings.is_empty()
+            && self.sort.is_none()
+    }
+}
+
+/// Which search filters a provider honours, mirroring `search-capabilities` of the `library-v2` world
+#[derive(ComponentType, Lift, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[component(record)]
+pub struct SearchCapabilities {
+    #[component(name = "include-tags")]
+    pub include_tags: bool,
+    #[component(name = "exclude-tags")]
+    pub exclude_tags: bool,
+    /// Tags the provider knows; empty when any tag is accepted
+    pub tags: Vec<String>,
+    pub statuses: Vec<PublicationStatus>,
+    pub languages: Vec<String>,
+    #[component(name = "year-range")]
+    pub year_range: bool,
+    #[component(name = "content-ratings")]
+    pub content_ratings: Vec<ContentRating>,
+    #[component(name = "sort-orders")]
+    pub sort_orders: Vec<SortOrder>,
+}
+
+impl SearchCapabilities {
+    /// Why the request cannot be sent to a provider with these capabilities, if it cannot
+    pub fn unsupported_filter(&self, request: &SearchRequest) -> Option<String> {
+        if !request.include_tags.is_empty() && !self.include_tags {
+            return Some("tag inclusion is not supported".to_string());
+        }
+        if !request.exclude_tags.is_empty() && !self.exclude_tags {
+            return Some("tag exclusion is not supported".to_string());
+        }
+        if !self.tags.is_empty() {
+            let unknown = request.include_tags.iter()
+                .chain(&request.exclude_tags)
+                .find(|t| !self.tags.iter().any(|known| known.eq_ignore_ascii_case(t)));
+            if let Some(tag) = unknown {
+                return Some(format!("unknown tag {:?}", tag));
+            }
+        }
+        if let Some(status) = request.status {
+            if !self.statuses.contains(&status) {
+                return Some(format!("status {:?} is not supported", status));
+            }
+        }
+        if let Some(lang) = request.languages.iter().find(|l| !self.languages.iter().any(|s| s.eq_ignore_ascii_case(l))) {
+            return Some(format!("language {:?} is not supported", lang));
+        }
+        if let Some(year) = request.year {
+            if !self.year_range {
+                return Some("year filtering is not supported".to_string());
+            }
+            if let (Some(min), Some(max)) = (year.min, year.max) {
+                if min > max {
+                    return Some(format!("year range {}..={} is empty", min, max));
+                }
+            }
+        }
+        if let Some(rating) = request.content_ratings.iter().find(|r| !self.content_ratings.contains(r)) {
+            return Some(format!("content rating {:?} is not supported", rating));
+        }
+        if let Some(sort) = request.sort {
+            if !self.sort_orders.contains(&sort) {
+                return Some(format!("sort order {:?} is not supported", sort));
+            }
+        }
+        None
+    }
+}
+
+/// Validate a request before it reaches the provider. Without search capabilities only plain requests pass.
+pub(crate) fn check_request(plugin: &str, caps: Option<&SearchCapabilities>, request: &SearchRequest) -> anyhow::Result<()> {
+    let reason = match caps {
+        Some(caps) => caps.unsupported_filter(request),
+        None if request.is_plain() => None,
+        None => Some("structured search is not supported".to_string()),
+    };
+    match reason {
+        Some(reason) => Err(PluginError::InvalidRequest { plugin: plugin.to_string(), reason }.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps() -> SearchCapabilities {
+        SearchCapabilities {
+            include_tags: true,
+            exclude_tags: false,
+            tags: vec!["Action".to_string(), "Drama".to_string()],
+            statuses: vec![PublicationStatus::Ongoing],
+            languages: vec!["en".to_string()],
+            year_range: true,
+            content_ratings: vec![ContentRating::Safe],
+            sort_orders: vec![SortOrder::Relevance],
+        }
+    }
+
+    fn request() -> SearchRequest {
+        SearchRequest::new(MediaType::Paged, "query")
+    }
+
+    #[test]
+    fn supported_filters_pass() {
+        let mut r = request();
+        r.include_tags = vec!["action".to_string()];
+        r.status = Some(PublicationStatus::Ongoing);
+        r.languages = vec!["EN".to_string()];
+        r.year = Some(YearRange { min: Some(2000), max: Some(2000) });
+        r.content_ratings = vec![ContentRating::Safe];
+        r.sort = Some(SortOrder::Relevance);
+        assert_eq!(caps().unsupported_filter(&r), None);
+    }
+
+    #[test]
+    fn unsupported_filters_are_named() {
+        let c = caps();
+        let mut r = request();
+        r.exclude_tags = vec!["Drama".to_string()];
+        assert!(c.unsupported_filter(&r).unwrap().contains("exclusion"));
+
+        let mut r = request();
+        r.include_tags = vec!["Romance".to_string()];
+        assert!(c.unsupported_filter(&r).unwrap().contains("unknown tag"));
+
+        let mut r = request();
+        r.status = Some(PublicationStatus::Hiatus);
+        assert!(c.unsupported_filter(&r).is_some());
+
+        let mut r = request();
+        r.languages = vec!["ja".to_string()];
+        assert!(c.unsupported_filter(&r).is_some());
+
+        let mut r = request();
+        r.content_ratings = vec![ContentRating::Explicit];
+        assert!(c.unsupported_filter(&r).is_some());
+
+        let mut r = request();
+        r.sort = Some(SortOrder::Newest);
+        assert!(c.unsupported_filter(&r).is_some());
+    }
+
+    #[test]
+    fn any_tag_is_accepted_without_a_tag_list() {
+        let c = SearchCapabilities { include_tags: true, ..SearchCapabilities::default() };
+        let mut r = request();
+        r.include_tags = vec!["Anything".to_string()];
+        assert_eq!(c.unsupported_filter(&r), None);
+    }
+
+    #[test]
+    fn year_ranges() {
+        let mut r = request();
+        r.year = Some(YearRange { min: Some(2010), max: Some(2001) });
+        assert!(caps().unsupported_filter(&r).unwrap().contains("empty"));
+        r.year = Some(YearRange { min: Some(2010), max: None });
+        assert_eq!(caps().unsupported_filter(&r), None);
+        let c = SearchCapabilities { year_range: false, ..caps() };
+        assert!(c.unsupported_filter(&r).unwrap().contains("year filtering"));
+    }
+
+    #[test]
+    fn without_capabilities_only_plain_requests_pass() {
+        assert!(check_request("p", None, &request()).is_ok());
+        let mut r = request();
+        r.sort = Some(SortOrder::Title);
+        let err = check_request("p", None, &r).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PluginError>(), Some(PluginError::InvalidRequest { .. })));
+    }
+}
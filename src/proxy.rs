@@ -0,0 +1,561 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::TryStreamExt;
+use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
+use hyper::header::{self, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::downloads::url_permitted;
+use crate::plugins::{apply_headers, Asset, AssetReader, AssetTransform, HostPolicy};
+
+/// Redirects followed per request; each hop is checked against the plugin's allowed hosts
+const MAX_REDIRECTS: usize = 5;
+/// Largest image buffered for the cache or a transform; bigger responses are streamed through uncached or refused
+const MAX_CACHED_IMAGE: u64 = 32 * 1024 * 1024;
+/// Longest content type line read when answering HEAD from the cache
+const MAX_CONTENT_TYPE_LINE: u64 = 1024;
+/// Proxy URLs remembered before the oldest stop resolving
+const MAX_ROUTES: usize = 50_000;
+/// Response headers passed through from upstream
+const FORWARDED_HEADERS: &[header::HeaderName] = &[
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::LAST_MODIFIED,
+    header::ETAG,
+];
+
+type ProxyBody = BoxBody<Bytes, reqwest::Error>;
+
+/// Tuning for the asset proxy
+#[derive(Debug, Clone)]
+pub struct ProxyOptions {
+    /// Directory for cached images; images are not cached when None
+    pub cache_dir: Option<PathBuf>,
+    /// Cache size above which the least recently used images are evicted
+    pub max_cache_bytes: u64,
+    pub connect_timeout: Duration,
+    /// Longest wait for the next chunk of an upstream response
+    pub read_timeout: Duration,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            cache_dir: None,
+            max_cache_bytes: 256 * 1024 * 1024,
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Loopback HTTP server that fetches assets on behalf of frontends, applying the owning plugin's
+/// required headers and allowed hosts. URLs are only valid for this process: they carry a random token
+/// and resolve through routes registered by `rewrite`. The server stops when the proxy is dropped.
+pub struct AssetProxy {
+    inner: Arc<Inner>,
+    addr: SocketAddr,
+    server: JoinHandle<()>,
+}
+
+struct Inner {
+    token: String,
+    client: reqwest::Client,
+    routes: Mutex<Routes>,
+    cache: Option<ImageCache>,
+}
+
+/// Where a proxy URL leads
+#[derive(Clone)]
+struct Route {
+    /// The asset as returned by the plugin, with its required headers
+    asset: Asset,
+    source: Source,
+}
+
+/// How a route's body is obtained
+#[derive(Clone)]
+enum Source {
+    Remote(Remote),
+    /// An image file read through a built-in provider
+    Local(AssetReader),
+    /// An image whose plugin transform could not be determined; it is never served untransformed
+    Unavailable,
+}
+
+/// Fetched from the asset's URL within the plugin's allowed hosts
+#[derive(Clone)]
+struct Remote {
+    policy: HostPolicy,
+    /// Images are served from and stored in the disk cache
+    cacheable: bool,
+    /// Applied to image bodies before they are cached or served
+    transform: Option<AssetTransform>,
+}
+
+#[derive(Default)]
+struct Routes {
+    by_key: HashMap<String, Route>,
+    order: VecDeque<String>,
+}
+
+impl AssetProxy {
+    /// Bind to a random loopback port and start serving
+    pub async fn start(options: ProxyOptions) -> Result<Self> {
+        let cache = match &options.cache_dir {
+            Some(dir) => Some(ImageCache::open(dir, options.max_cache_bytes).await?),
+            None => None,
+        };
+        let client = reqwest::Client::builder()
+            .user_agent(concat!("awasmlib/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::none())
+            .connect_timeout(options.connect_timeout)
+            .read_timeout(options.read_timeout)
+            .build()?;
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await
+            .context("failed to bind asset proxy")?;
+        let addr = listener.local_addr()?;
+        let inner = Arc::new(Inner { token: random_token()?, client, routes: Mutex::new(Routes::default()), cache });
+        let server = tokio::spawn(serve(listener, inner.clone()));
+        info!(%addr, "asset proxy listening");
+        Ok(Self { inner, addr, server })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Point http(s) assets the plugin may reach at the proxy. The proxy applies their headers and the
+    /// plugin's image transform, so the rewritten assets carry no headers. With a `reader`, `file:` images
+    /// are served through it as well. Other assets are left unchanged. When the transform could not be
+    /// determined, image requests fail with 502 instead of reaching upstream.
+    pub(crate) fn rewrite(
+        &self,
+        plugin: &str,
+        assets: &mut [Asset],
+        policy: &HostPolicy,
+        reader: Option<AssetReader>,
+        transform: &Result<Option<AssetTransform>>,
+    ) {
+        let mut routes = self.inner.routes.lock().unwrap();
+        for asset in assets.iter_mut() {
+            let source = if url_permitted(policy, &asset.url) {
+                match transform {
+                    Err(_) if asset.is_image() => Source::Unavailable,
+                    _ => Source::Remote(Remote {
+                        policy: policy.clone(),
+                        cacheable: asset.is_image(),
+                        transform: transform.as_ref().ok().cloned().flatten().filter(|_| asset.is_image()),
+                    }),
+                }
+            } else if let Some(reader) = reader.clone().filter(|_| asset.url.starts_with("file:") && asset.is_image()) {
+                Source::Local(reader)
+            } else {
+                continue;
+            };
+            let key = route_key(plugin, &asset.url);
+            let route = Route { asset: asset.clone(), source };
+            asset.headers.clear();
+            if routes.by_key.insert(key.clone(), route).is_none() {
+                routes.order.push_back(key.clone());
+            }
+            asset.url = format!("http://{}/{}/{}", self.addr, self.inner.token, key);
+        }
+        while routes.order.len() > MAX_ROUTES {
+            if let Some(old) = routes.order.pop_front() {
+                routes.by_key.remove(&old);
+            }
+        }
+    }
+
+    /// Remove every cached image
+    pub async fn clear_cache(&self) -> Result<()> {
+        match &self.inner.cache {
+            Some(cache) => cache.clear().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AssetProxy {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, inner: Arc<Inner>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!(error=%e, "asset proxy accept failed");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| {
+                let inner = inner.clone();
+                async move { Ok::<_, Infallible>(inner.handle(req).await) }
+            });
+            if let Err(e) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                debug!(error=%e, "asset proxy connection ended");
+            }
+        });
+    }
+}
+
+impl Inner {
+    async fn handle(&self, req: Request<Incoming>) -> Response<ProxyBody> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return status_response(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        let head = req.method() == Method::HEAD;
+        let mut segments = req.uri().path().trim_start_matches('/').splitn(2, '/');
+        let (Some(token), Some(key)) = (segments.next(), segments.next()) else {
+            return status_response(StatusCode::NOT_FOUND);
+        };
+        if token != self.token {
+            return status_response(StatusCode::NOT_FOUND);
+        }
+        let Some(route) = self.routes.lock().unwrap().by_key.get(key).cloned() else {
+            return status_response(StatusCode::NOT_FOUND);
+        };
+        match &route.source {
+            Source::Remote(remote) => self.handle_remote(&req, key, &route.asset, remote, head).await,
+            Source::Local(reader) => match reader.read(&route.asset.url).await {
+                Ok(body) => {
+                    let content_type = route.asset.mime.as_deref().unwrap_or("application/octet-stream");
+                    body_response(StatusCode::OK, content_type, Bytes::from(body), head)
+                }
+                Err(e) => {
+                    warn!(url=%route.asset.url, error=%e, "asset proxy read failed");
+                    status_response(StatusCode::BAD_GATEWAY)
+                }
+            },
+            Source::Unavailable => status_response(StatusCode::BAD_GATEWAY),
+        }
+    }
+
+    async fn handle_remote(&self, req: &Request<Incoming>, key: &str, asset: &Asset, remote: &Remote, head: bool) -> Response<ProxyBody> {
+        let cache = self.cache.as_ref().filter(|_| remote.cacheable);
+        let transform = remote.transform.as_ref();
+        if let Some(cache) = cache {
+            if head {
+                if let Some((content_type, len)) = cache.peek(key).await {
+                    return head_response(&content_type, len);
+                }
+            } else if let Some((content_type, body)) = cache.get(key).await {
+                return body_response(StatusCode::OK, &content_type, body, false);
+            }
+        }
+        // Transformed images are always fetched whole, since the transform needs the complete body
+        let buffered = cache.is_some() || transform.is_some();
+        let range = req.headers().get(header::RANGE).filter(|_| !buffered).cloned();
+        // HEAD is forwarded as such; nothing is buffered, transformed or cached for it
+        let method = if head { Method::HEAD } else { Method::GET };
+        let upstream = match self.fetch(asset, &remote.policy, method, range).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                warn!(url=%asset.url, error=%e, "asset proxy fetch failed");
+                return status_response(StatusCode::BAD_GATEWAY);
+            }
+        };
+        let status = upstream.status();
+        // Without a transform, images of unknown or excessive size are streamed through uncached
+        let fits = upstream.content_length().map_or(transform.is_some(), |len| len <= MAX_CACHED_IMAGE);
+        if !head && buffered && status == StatusCode::OK && (fits || transform.is_some()) {
+            let upstream_type = upstream.headers().get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/octet-stream")
+                .to_string();
+            let body = match read_capped(upstream, MAX_CACHED_IMAGE).await {
+                Ok(Some(body)) => body,
+                Ok(None) => {
+                    warn!(url=%asset.url, "image too large to buffer");
+                    return status_response(StatusCode::BAD_GATEWAY);
+                }
+                Err(e) => {
+                    warn!(url=%asset.url, error=%e, "asset proxy read failed");
+                    return status_response(StatusCode::BAD_GATEWAY);
+                }
+            };
+            let (content_type, body) = match transform {
+                Some(transform) => match transform.apply(asset, body).await {
+                    // The upstream type describes the scrambled bytes; the asset's own mime is more reliable
+                    Ok(body) => (asset.mime.clone().unwrap_or(upstream_type), Bytes::from(body)),
+                    Err(e) => {
+                        warn!(url=%asset.url, error=%e, "asset transform failed");
+                        return status_response(StatusCode::BAD_GATEWAY);
+                    }
+                },
+                None => (upstream_type, Bytes::from(body)),
+            };
+            if let Some(cache) = cache {
+                if let Err(e) = cache.put(key, &content_type, &body).await {
+                    debug!(url=%asset.url, error=%e, "image not cached");
+                }
+            }
+            return body_response(status, &content_type, body, false);
+        }
+
+        let mut builder = Response::builder().status(status);
+        for name in FORWARDED_HEADERS {
+            // Length, ranges and validators of scrambled bytes do not describe the transformed image
+            if transform.is_some() && *name != header::CONTENT_TYPE {
+                continue;
+            }
+            if let Some(value) = upstream.headers().get(name) {
+                builder = builder.header(name, value);
+            }
+        }
+        let body = if head {
+            empty_body()
+        } else {
+            StreamBody::new(upstream.bytes_stream().map_ok(Frame::data)).boxed()
+        };
+        builder.body(body).unwrap_or_else(|_| status_response(StatusCode::BAD_GATEWAY))
+    }
+
+    /// Request the route's URL with its headers, following redirects only within the plugin's allowed hosts
+    async fn fetch(&self, asset: &Asset, policy: &HostPolicy, method: Method, range: Option<HeaderValue>) -> Result<reqwest::Response> {
+        let mut url = Url::parse(&asset.url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            if !url_permitted(policy, url.as_str()) {
+                bail!("{} is not within the plugin's allowed hosts", url);
+            }
+            let mut req = apply_headers(self.client.request(method.clone(), url.clone()), &asset.headers);
+            if let Some(range) = &range {
+                req = req.header(header::RANGE, range);
+            }
+            let resp = req.send().await?;
+            if !resp.status().is_redirection() {
+                return Ok(resp);
+            }
+            let location = resp.headers().get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow!("redirect without location from {}", url))?;
+            url = url.join(location)?;
+        }
+        bail!("too many redirects")
+    }
+}
+
+/// Read a whole response body, or None as soon as it grows past `cap`
+async fn read_capped(mut resp: reqwest::Response, cap: u64) -> Result<Option<Vec<u8>>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        if (body.len() + chunk.len()) as u64 > cap {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(Some(body))
+}
+
+fn status_response(status: StatusCode) -> Response<ProxyBody> {
+    let mut resp = Response::new(empty_body());
+    *resp.status_mut() = status;
+    resp
+}
+
+fn body_response(status: StatusCode, content_type: &str, body: Bytes, head: bool) -> Response<ProxyBody> {
+    let len = body.len();
+    let body = if head { empty_body() } else { Full::new(body).map_err(|never| match never {}).boxed() };
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, len)
+        .body(body)
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Answer to a HEAD request for a cached image
+fn head_response(content_type: &str, len: u64) -> Response<ProxyBody> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, len)
+        .body(empty_body())
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+fn empty_body() -> ProxyBody {
+    Empty::new().map_err(|never| match never {}).boxed()
+}
+
+/// Stable per plugin and URL, so cached images survive restarts
+fn route_key(plugin: &str, url: &str) -> String {
+    let digest = Sha256::new().chain_update(plugin.as_bytes()).chain_update([0]).chain_update(url.as_bytes()).finalize();
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("failed to generate proxy token: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Images on disk, one file per route key holding the content type on the first line followed by the body.
+/// File modification times record last use, so eviction order survives restarts.
+struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    /// Size and last use of each entry
+    entries: HashMap<String, (u64, SystemTime)>,
+    total: u64,
+}
+
+impl ImageCache {
+    async fn open(dir: &Path, max_bytes: u64) -> Result<Self> {
+        let scan_dir = dir.to_path_buf();
+        let index = tokio::task::spawn_blocking(move || scan(&scan_dir)).await??;
+        let cache = Self { dir: dir.to_path_buf(), max_bytes, index: Mutex::new(index) };
+        cache.evict().await;
+        Ok(cache)
+    }
+
+    async fn get(&self, key: &str) -> Option<(String, Bytes)> {
+        if !self.index.lock().unwrap().entries.contains_key(key) {
+            return None;
+        }
+        let path = self.dir.join(key);
+        let data = tokio::fs::read(&path).await.ok()?;
+        let split = data.iter().position(|b| *b == b'\n')?;
+        let content_type = String::from_utf8(data[..split].to_vec()).ok()?;
+        let now = SystemTime::now();
+        if let Some(entry) = self.index.lock().unwrap().entries.get_mut(key) {
+            entry.1 = now;
+        }
+        tokio::task::spawn_blocking(move || {
+            let _ = std::fs::File::options().write(true).open(&path).and_then(|f| f.set_modified(now));
+        });
+        Some((content_type, Bytes::from(data).slice(split + 1..)))
+    }
+
+    /// Content type and body length of an entry, without reading the body
+    async fn peek(&self, key: &str) -> Option<(String, u64)> {
+        let size = self.index.lock().unwrap().entries.get(key)?.0;
+        let file = tokio::fs::File::open(self.dir.join(key)).await.ok()?;
+        let mut line = String::new();
+        tokio::io::BufReader::new(file).take(MAX_CONTENT_TYPE_LINE).read_line(&mut line).await.ok()?;
+        let content_type = line.strip_suffix('\n')?;
+        Some((content_type.to_string(), size.checked_sub(line.len() as u64)?))
+    }
+
+    async fn put(&self, key: &str, content_type: &str, body: &[u8]) -> Result<()> {
+        let path = self.dir.join(key);
+        // Concurrent misses for one image each write their own file; the last rename wins
+        let tmp = crate::tmp::sibling(&path);
+        let mut data = Vec::with_capacity(content_type.len() + 1 + body.len());
+        data.extend_from_slice(content_type.as_bytes());
+        data.push(b'\n');
+        data.extend_from_slice(body);
+        let written = match tokio::fs::write(&tmp, &data).await {
+            Ok(()) => tokio::fs::rename(&tmp, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e.into());
+        }
+        {
+            let mut index = self.index.lock().unwrap();
+            let size = data.len() as u64;
+            if let Some((old, _)) = index.entries.insert(key.to_string(), (size, SystemTime::now())) {
+                index.total -= old;
+            }
+            index.total += size;
+        }
+        self.evict().await;
+        Ok(())
+    }
+
+    /// Remove least recently used entries until the cache fits its budget
+    async fn evict(&self) {
+        let victims: Vec<String> = {
+            let mut index = self.index.lock().unwrap();
+            if index.total <= self.max_bytes {
+                return;
+            }
+            let mut by_age: Vec<(String, u64, SystemTime)> = index.entries.iter()
+                .map(|(k, (size, used))| (k.clone(), *size, *used))
+                .collect();
+            by_age.sort_by_key(|(_, _, used)| *used);
+            let mut victims = Vec::new();
+            for (key, size, _) in by_age {
+                if index.total <= self.max_bytes {
+                    break;
+                }
+                index.entries.remove(&key);
+                index.total -= size;
+                victims.push(key);
+            }
+            victims
+        };
+        debug!(count=victims.len(), "evicting cached images");
+        for key in victims {
+            let _ = tokio::fs::remove_file(self.dir.join(key)).await;
+        }
+    }
+
+    async fn clear(&self) -> Result<()> {
+        let keys: Vec<String> = {
+            let mut index = self.index.lock().unwrap();
+            index.total = 0;
+            index.entries.drain().map(|(k, _)| k).collect()
+        };
+        for key in keys {
+            match tokio::fs::remove_file(self.dir.join(&key)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Index the entries of a cache directory, creating it if needed and removing temporary files left by
+/// interrupted writes
+fn scan(dir: &Path) -> Result<CacheIndex> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create image cache directory {}", dir.display()))?;
+    let mut index = CacheIndex::default();
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let Ok(meta) = entry.metadata() else { continue };
+        if !meta.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.ends_with(".tmp") {
+            let _ = std::fs::remove_file(entry.path());
+            continue;
+        }
+        index.total += meta.len();
+        index.entries.insert(name, (meta.len(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+    }
+    Ok(index)
+}
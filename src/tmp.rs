@@ -0,0 +1,28 @@
+//! Temporary files written next to their destination and renamed into place
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary path beside `path` that no other write, in this or another process, uses at the same time
+pub(crate) fn sibling(path: &Path) -> PathBuf {
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!("{}.{}-{}.tmp", name, std::process::id(), n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siblings_are_unique_and_beside_the_path() {
+        let path = Path::new("/data/cache/plugin.cwasm");
+        let (a, b) = (sibling(path), sibling(path));
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), path.parent());
+        let name = a.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(name.starts_with("plugin.cwasm.") && name.ends_with(".tmp"), "{}", name);
+    }
+}